@@ -0,0 +1,127 @@
+//! Capture callback path: per-callback `Vec` + mpsc (the old path) vs the
+//! preallocated SPSC ring. Callbacks are paced (about 200x real time) while
+//! a consumer drains concurrently, polling like the chunker does, and the
+//! time spent inside each callback is what's measured: that's what decides
+//! whether capture glitches.
+//!
+//!     cargo bench --bench capture
+
+#[path = "../src/capture/ring.rs"]
+mod ring;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 48 kHz stereo, 512 frames: a typical CoreAudio callback.
+const BLOCK: usize = 1024;
+const BLOCKS: usize = 40_000;
+/// Callback period; real time would be ~10.7ms.
+const PERIOD: Duration = Duration::from_micros(50);
+/// Consumer poll interval, scaled like `PERIOD`.
+const POLL: Duration = Duration::from_millis(1);
+const RING_SAMPLES: usize = 48_000 * 2 * 10;
+
+struct Stats {
+    label: &'static str,
+    callbacks: Vec<Duration>,
+    received: usize,
+    overruns: usize,
+}
+
+impl Stats {
+    fn print(&mut self) {
+        self.callbacks.sort();
+        let n = self.callbacks.len();
+        let mean = self.callbacks.iter().sum::<Duration>() / n as u32;
+        let pct = |p: f64| self.callbacks[((n as f64 * p) as usize).min(n - 1)];
+        println!(
+            "{:<10} mean {:>8.0?}  p50 {:>8.0?}  p99 {:>8.0?}  p99.9 {:>8.0?}  max {:>8.0?}  received {}  overruns {}",
+            self.label,
+            mean,
+            pct(0.5),
+            pct(0.99),
+            pct(0.999),
+            self.callbacks[n - 1],
+            self.received,
+            self.overruns,
+        );
+    }
+}
+
+fn block() -> Vec<f32> {
+    (0..BLOCK).map(|i| (i as f32 * 0.01).sin()).collect()
+}
+
+/// Run `callback` every `PERIOD`, timing each call.
+fn drive(mut callback: impl FnMut()) -> Vec<Duration> {
+    let mut callbacks = Vec::with_capacity(BLOCKS);
+    let mut next = Instant::now();
+    for _ in 0..BLOCKS {
+        while Instant::now() < next {
+            std::hint::spin_loop();
+        }
+        next += PERIOD;
+        let t = Instant::now();
+        callback();
+        callbacks.push(t.elapsed());
+    }
+    callbacks
+}
+
+fn bench_mpsc() -> Stats {
+    let data = block();
+    let (tx, rx) = mpsc::channel::<Vec<f32>>();
+    let consumer = thread::spawn(move || {
+        let mut out = Vec::with_capacity(BLOCK * BLOCKS);
+        while let Ok(chunk) = rx.recv() {
+            out.extend(chunk);
+        }
+        out.len()
+    });
+
+    let callbacks = drive(|| {
+        let _ = tx.send(data.to_vec());
+    });
+    drop(tx);
+    let received = consumer.join().unwrap();
+    Stats { label: "mpsc+Vec", callbacks, received, overruns: 0 }
+}
+
+fn bench_ring() -> Stats {
+    let data = block();
+    let (producer, consumer) = ring::ring(RING_SAMPLES);
+    let done = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            let mut out = Vec::with_capacity(BLOCK * BLOCKS);
+            loop {
+                let finished = done.load(Ordering::Acquire);
+                if consumer.read_into(&mut out) == 0 {
+                    if finished {
+                        break;
+                    }
+                    thread::sleep(POLL);
+                }
+            }
+            (out.len(), consumer.overruns())
+        })
+    };
+
+    let callbacks = drive(|| {
+        producer.push(&data);
+    });
+    done.store(true, Ordering::Release);
+    let (received, overruns) = reader.join().unwrap();
+    Stats { label: "ring", callbacks, received, overruns }
+}
+
+fn main() {
+    println!("{BLOCKS} callbacks of {BLOCK} samples\n");
+    for _ in 0..3 {
+        bench_mpsc().print();
+        bench_ring().print();
+    }
+}
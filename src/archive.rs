@@ -0,0 +1,207 @@
+use crate::encode::FlacWriter;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Chunk boundary in the archive, also appended to `{name}.cues.jsonl`
+/// as it happens so a crash doesn't lose them.
+#[derive(Serialize)]
+struct Cue {
+    frame: u64,
+    seconds: f64,
+    label: String,
+}
+
+/// 16-bit PCM WAV whose header is rewritten after every append, so the
+/// file on disk is always valid up to the last write.
+struct WavStream {
+    file: File,
+    channels: u16,
+    frames: u64,
+}
+
+const WAV_HEADER_LEN: u64 = 44;
+
+impl WavStream {
+    fn create(path: &Path, sample_rate: u32, channels: u16) -> std::io::Result<Self> {
+        let mut file = File::create(path)?;
+        let block_align = channels * 2;
+        let mut header = Vec::with_capacity(WAV_HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&36u32.to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        header.extend_from_slice(&channels.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        file.write_all(&header)?;
+        Ok(Self { file, channels, frames: 0 })
+    }
+
+    fn data_len(&self) -> u64 {
+        self.frames * self.channels as u64 * 2
+    }
+
+    fn append(&mut self, samples: &[i16]) -> std::io::Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.file.seek(SeekFrom::Start(WAV_HEADER_LEN + self.data_len()))?;
+        self.file.write_all(&bytes)?;
+        self.frames += (samples.len() / self.channels as usize) as u64;
+        self.update_sizes(0)?;
+        self.file.sync_data()
+    }
+
+    /// Point the RIFF and data sizes at what's been written. `trailer` is
+    /// the length of any chunks after the data.
+    fn update_sizes(&mut self, trailer: u64) -> std::io::Result<()> {
+        let data = self.data_len();
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&((36 + data + trailer) as u32).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&(data as u32).to_le_bytes())
+    }
+
+    /// Append `cue ` and `LIST/adtl` label chunks after the audio.
+    fn finish(mut self, cues: &[Cue]) -> std::io::Result<()> {
+        if cues.is_empty() {
+            return Ok(());
+        }
+
+        let mut cue = Vec::new();
+        cue.extend_from_slice(&(cues.len() as u32).to_le_bytes());
+        for (id, c) in cues.iter().enumerate() {
+            cue.extend_from_slice(&(id as u32 + 1).to_le_bytes());
+            cue.extend_from_slice(&(c.frame as u32).to_le_bytes());
+            cue.extend_from_slice(b"data");
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(&(c.frame as u32).to_le_bytes());
+        }
+
+        let mut adtl = b"adtl".to_vec();
+        for (id, c) in cues.iter().enumerate() {
+            let mut text = c.label.as_bytes().to_vec();
+            text.push(0);
+            adtl.extend_from_slice(b"labl");
+            adtl.extend_from_slice(&(4 + text.len() as u32).to_le_bytes());
+            adtl.extend_from_slice(&(id as u32 + 1).to_le_bytes());
+            if text.len() % 2 == 1 {
+                text.push(0);
+            }
+            adtl.extend_from_slice(&text);
+        }
+
+        let mut trailer = Vec::new();
+        for (id, body) in [(b"cue ", cue), (b"LIST", adtl)] {
+            trailer.extend_from_slice(id);
+            trailer.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            trailer.extend_from_slice(&body);
+        }
+
+        self.file.seek(SeekFrom::Start(WAV_HEADER_LEN + self.data_len()))?;
+        self.file.write_all(&trailer)?;
+        self.update_sizes(trailer.len() as u64)?;
+        self.file.sync_data()
+    }
+}
+
+enum Writer {
+    Wav(WavStream),
+    Flac(FlacWriter<File>),
+}
+
+/// One continuous multi-channel recording of the whole session (WAV or
+/// FLAC by extension), fed as chunks are cut. Finalized on `finish` or drop.
+pub struct Archive {
+    path: PathBuf,
+    sample_rate: u32,
+    channels: u16,
+    writer: Option<Writer>,
+    frames: u64,
+    cues: Vec<Cue>,
+    cue_log: File,
+}
+
+impl Archive {
+    pub fn create(path: &Path, sample_rate: u32, channels: u16) -> Result<Self, Box<dyn std::error::Error>> {
+        let writer = match path.extension().and_then(|e| e.to_str()) {
+            Some("wav") => Writer::Wav(WavStream::create(path, sample_rate, channels)?),
+            Some("flac") => Writer::Flac(FlacWriter::new(File::create(path)?, sample_rate, channels)?),
+            _ => return Err(format!("archive must be .wav or .flac: {}", path.display()).into()),
+        };
+        let cue_log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.with_extension("cues.jsonl"))?;
+
+        eprintln!("Archiving session audio to {}", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            sample_rate,
+            channels,
+            writer: Some(writer),
+            frames: 0,
+            cues: Vec::new(),
+            cue_log,
+        })
+    }
+
+    /// Frames appended so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Append interleaved samples.
+    pub fn append(&mut self, samples: &[i16]) -> Result<(), Box<dyn std::error::Error>> {
+        match self.writer.as_mut() {
+            Some(Writer::Wav(w)) => w.append(samples)?,
+            Some(Writer::Flac(w)) => w.write(samples)?,
+            None => return Err("archive already finished".into()),
+        }
+        self.frames += (samples.len() / self.channels as usize) as u64;
+        Ok(())
+    }
+
+    /// Mark `frame` with `label` (a chunk's timestamp).
+    pub fn cue(&mut self, frame: u64, label: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let cue = Cue {
+            frame,
+            seconds: frame as f64 / self.sample_rate as f64,
+            label: label.to_string(),
+        };
+        writeln!(self.cue_log, "{}", serde_json::to_string(&cue)?)?;
+        self.cues.push(cue);
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.finalize()
+    }
+
+    fn finalize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.writer.take() {
+            Some(Writer::Wav(w)) => w.finish(&self.cues)?,
+            Some(Writer::Flac(w)) => {
+                w.finish()?.sync_data()?;
+            }
+            None => return Ok(()),
+        }
+        let secs = self.frames as f64 / self.sample_rate as f64;
+        eprintln!("Archived {secs:.1}s of audio to {}", self.path.display());
+        Ok(())
+    }
+}
+
+impl Drop for Archive {
+    fn drop(&mut self) {
+        if let Err(e) = self.finalize() {
+            eprintln!("Failed to finalize {}: {e}", self.path.display());
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
 pub fn write_wav_i16(
     path: &str,
     samples: &[i16],
@@ -28,3 +31,132 @@ pub fn write_wav_i16(
 
     Ok(())
 }
+
+/// Container/codec for chunk files and uploads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Wav,
+    Flac,
+    Opus,
+}
+
+impl AudioFormat {
+    pub fn parse(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match name {
+            "wav" => Ok(Self::Wav),
+            "flac" => Ok(Self::Flac),
+            "opus" | "ogg" => Ok(Self::Opus),
+            other => Err(format!("unknown audio format '{other}' (expected wav, flac or opus)").into()),
+        }
+    }
+
+    /// Format of an existing file, by extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::parse(&ext).ok()
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Flac => "flac",
+            Self::Opus => "ogg",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Wav => "audio/wav",
+            Self::Flac => "audio/flac",
+            Self::Opus => "audio/ogg",
+        }
+    }
+}
+
+/// Encode interleaved 16-bit PCM in `format`.
+pub fn encode(
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+    format: AudioFormat,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match format {
+        AudioFormat::Wav => {
+            let spec = hound::WavSpec {
+                channels,
+                sample_rate,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut cursor = std::io::Cursor::new(Vec::new());
+            let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+            for &sample in samples {
+                writer.write_sample(sample)?;
+            }
+            writer.finalize()?;
+            Ok(cursor.into_inner())
+        }
+        AudioFormat::Flac => Ok(crate::encode::flac(samples, sample_rate, channels)),
+        AudioFormat::Opus => crate::encode::opus(samples, sample_rate, channels),
+    }
+}
+
+/// Like `write_wav_i16`, in any format. `path` should carry `format.extension()`.
+pub fn write_audio(
+    path: &Path,
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+    format: AudioFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if format == AudioFormat::Wav {
+        return write_wav_i16(path.to_str().unwrap(), samples, sample_rate, channels);
+    }
+    if samples.is_empty() {
+        eprintln!("No audio captured.");
+        return Ok(());
+    }
+
+    fs::write(path, encode(samples, sample_rate, channels, format)?)?;
+
+    let frames = samples.len() / channels as usize;
+    let duration_secs = frames as f64 / sample_rate as f64;
+    eprintln!("Wrote {duration_secs:.1}s of audio to {}", path.display());
+    Ok(())
+}
+
+/// Samples, rate and channel count of a 16-bit WAV file.
+pub fn read_wav_i16(path: &Path) -> Result<(Vec<i16>, u32, u16), Box<dyn std::error::Error>> {
+    let reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples = reader.into_samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+    Ok((samples, spec.sample_rate, spec.channels))
+}
+
+/// Write channel `channel` of a 16-bit WAV file to `out` as a mono WAV.
+pub fn extract_channel(wav: &Path, channel: u16, out: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let (samples, rate, channels) = read_wav_i16(wav)?;
+    if channel >= channels {
+        return Err(format!("{} has no channel {channel}", wav.display()).into());
+    }
+    let mono: Vec<i16> = samples
+        .iter()
+        .skip(channel as usize)
+        .step_by(channels as usize)
+        .copied()
+        .collect();
+    write_wav_i16(out.to_str().unwrap(), &mono, rate, 1)
+}
+
+/// Re-encode a WAV file next to itself in `format` and remove the WAV.
+/// Returns the path of the file that now holds the audio.
+pub fn transcode(wav: &Path, format: AudioFormat) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if format == AudioFormat::Wav {
+        return Ok(wav.to_path_buf());
+    }
+    let (samples, rate, channels) = read_wav_i16(wav)?;
+    let path = wav.with_extension(format.extension());
+    fs::write(&path, encode(&samples, rate, channels, format)?)?;
+    fs::remove_file(wav)?;
+    Ok(path)
+}
@@ -0,0 +1,534 @@
+use crate::audio::{self, AudioFormat};
+use crate::capture::Source;
+use crate::chunker::local_timestamp_at;
+use crate::pipeline::{self, ChunkSet, PipelineConfig, RenderOptions, Track};
+use crate::session::{Session, SessionMeta};
+use crate::status::Status;
+use crate::transcribe::TranscribeConfig;
+use crate::{decode, mixer, vad};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::sync_channel;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+const TARGET_RATE: u32 = 16000;
+
+/// How far back from a piece's full length to look for a quiet place to cut.
+const CUT_WINDOW_SECS: f64 = 5.0;
+
+/// Shortest `--chunk-duration` for a file. Cuts fall at most halfway back,
+/// so pieces are at least a second long and no two are named for the same
+/// second.
+const MIN_PIECE_SECS: u32 = 2;
+
+/// Options for transcribing existing recordings.
+pub struct BatchConfig {
+    pub output_dir: String,
+    /// Piece length in seconds, like `--chunk-duration` when capturing.
+    pub chunk_duration: u32,
+    pub concurrency: usize,
+    /// Who a single-track recording's segments are attributed to.
+    pub speaker: String,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    /// Backend recorded in `session.json`.
+    pub backend: String,
+    /// Markdown transcript of a single file; `transcript-{session id}.md`
+    /// if unset.
+    pub transcript_path: Option<PathBuf>,
+    pub markdown: RenderOptions,
+}
+
+/// Decode `path`, cut it into pieces at quiet points and transcribe them
+/// on the worker pool into a new session, as if it had been recorded live.
+pub fn transcribe_file(
+    path: &Path,
+    transcribe: TranscribeConfig,
+    config: &BatchConfig,
+) -> Result<Session, Box<dyn std::error::Error>> {
+    let info = decode::probe(path)?;
+    let length = info.duration_secs.map(|d| format!("{d:.1}s, ")).unwrap_or_default();
+    eprintln!("{}: {length}{}Hz, {}ch", path.display(), info.sample_rate, info.channels);
+
+    let title = config
+        .title
+        .clone()
+        .or_else(|| path.file_name().map(|n| n.to_string_lossy().to_string()));
+    let mut session = Session::create(&config.output_dir, SessionMeta {
+        title,
+        tags: config.tags.clone(),
+        mode: "file".to_string(),
+        backend: config.backend.clone(),
+        model: Some(transcribe.model.clone()),
+        source: Some(path.to_path_buf()),
+    })?;
+    let transcript_path = config
+        .transcript_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("transcript-{}.md", session.id)));
+    session.transcript_path = Some(transcript_path.clone());
+    session.save()?;
+    eprintln!("Session {} ({})", session.id, session.dir.display());
+
+    eprintln!("Transcribing with {} workers", config.concurrency);
+
+    let status = Arc::new(Status::new());
+    let (tx, rx) = sync_channel(config.concurrency);
+    let handles = pipeline::run(rx, PipelineConfig {
+        transcribe,
+        session: session.clone(),
+        transcript_path: transcript_path.clone(),
+        concurrency: config.concurrency,
+        save_audio: false,
+        audio_format: AudioFormat::Wav,
+        status: Arc::clone(&status),
+        markdown: config.markdown.clone(),
+        results_tx: None,
+    });
+
+    let dir = session.audio_dir();
+    fs::create_dir_all(&dir)?;
+    let send = |piece: vad::Piece| -> Result<(), Box<dyn std::error::Error>> {
+        let start = clock(piece.start / TARGET_RATE as usize);
+        let end = clock((piece.start + piece.samples.len()) / TARGET_RATE as usize);
+        let wav = write_chunk(&dir, &start, "file", &piece.samples)?;
+
+        status.chunk_queued(0);
+        let chunk = ChunkSet {
+            timestamp: start,
+            timestamp_end: Some(end),
+            name: None,
+            tracks: vec![Track {
+                label: "file".to_string(),
+                speaker: config.speaker.clone(),
+                source: Source::System,
+                path: wav,
+                channel: None,
+            }],
+            markers: Vec::new(),
+            bytes: 0,
+        };
+        tx.send(chunk).map_err(|_| "transcription workers stopped".into())
+    };
+
+    // Pieces go out as they're cut, so only the audio since the last cut
+    // is held; the channel bounds how far decoding runs ahead
+    let mut splitter = vad::Splitter::new(TARGET_RATE, config.chunk_duration.max(MIN_PIECE_SECS) as f64, CUT_WINDOW_SECS);
+    let mut pieces = 0;
+    let decoded = decode::decode_mono(path, TARGET_RATE, |samples| {
+        for piece in splitter.push(samples) {
+            send(piece)?;
+            pieces += 1;
+        }
+        Ok(())
+    });
+    match decoded {
+        Ok(secs) => {
+            if let Some(piece) = splitter.finish()
+                && send(piece).is_ok()
+            {
+                pieces += 1;
+            }
+            eprintln!("Decoded {secs:.1}s into {pieces} pieces");
+        }
+        Err(e) => eprintln!("Decoding stopped after {pieces} pieces: {e}"),
+    }
+    drop(tx);
+    pipeline::shutdown(handles);
+
+    let failed = status.failed.load(Ordering::SeqCst);
+    if failed > 0 {
+        eprintln!("{failed} pieces failed; their audio is kept in {}", dir.display());
+    }
+    if session.jsonl_path().exists() {
+        pipeline::rewrite_markdown(&session, &transcript_path, &config.markdown)?;
+    }
+    session.finish()?;
+    Ok(session)
+}
+
+/// A short recording decoded, downmixed and resampled to 16 kHz mono.
+fn load_mono(path: &Path) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+    let mut samples = Vec::new();
+    decode::decode_mono(path, TARGET_RATE, |s| {
+        samples.extend_from_slice(s);
+        Ok(())
+    })?;
+    Ok(samples)
+}
+
+/// Normalize `samples` and write them to `dir` as the `label` track of the
+/// chunk at `timestamp`.
+fn write_chunk(dir: &Path, timestamp: &str, label: &str, samples: &[f32]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut audio = samples.to_vec();
+    mixer::peak_normalize(&mut audio, 0.9);
+    let wav = dir.join(format!("{timestamp}_{label}.wav"));
+    audio::write_wav_i16(wav.to_str().unwrap(), &mixer::f32_to_i16(&audio), TARGET_RATE, 1)?;
+    Ok(wav)
+}
+
+/// A system/mic pair of chunk files kept by `--save-audio`:
+/// `{time}_system.{ext}` and `{time}_mic.{ext}`.
+pub struct Pair {
+    /// Day it was recorded, "2026-02-15".
+    pub date: String,
+    /// Chunk time, "14-30-05".
+    pub time: String,
+    /// The session it was recorded in (or else its directory's name), since
+    /// two sessions on one day can both have a chunk at the same time.
+    pub source: String,
+    pub system: PathBuf,
+    pub mic: PathBuf,
+}
+
+/// Pairs among `paths` (files, or directories searched recursively),
+/// oldest first, and how many chunk files had no partner.
+pub fn find_pairs(paths: &[PathBuf]) -> (Vec<Pair>, usize) {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_files(path, &mut files);
+    }
+
+    let mut halves: BTreeMap<(PathBuf, String), (Option<PathBuf>, Option<PathBuf>)> = BTreeMap::new();
+    for file in files {
+        if AudioFormat::from_path(&file).is_none() {
+            continue;
+        }
+        let Some((time, side)) = file.file_stem().and_then(|s| s.to_str()).and_then(|s| s.rsplit_once('_')) else {
+            continue;
+        };
+        if !is_clock(time) {
+            continue;
+        }
+        let dir = file.parent().unwrap_or(Path::new("")).to_path_buf();
+        let entry = halves.entry((dir, time.to_string())).or_default();
+        match side {
+            "system" => entry.0 = Some(file),
+            "mic" => entry.1 = Some(file),
+            _ => {}
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let mut unpaired = 0;
+    for ((_, time), halves) in halves {
+        match halves {
+            (Some(system), Some(mic)) => pairs.push(Pair {
+                date: recording_date(&system),
+                source: source_name(&system),
+                time,
+                system,
+                mic,
+            }),
+            (None, None) => {}
+            _ => unpaired += 1,
+        }
+    }
+    pairs.sort_by(|a, b| (&a.date, &a.time).cmp(&(&b.date, &b.time)));
+    (pairs, unpaired)
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return;
+    }
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            collect_files(&entry.path(), files);
+        }
+    }
+}
+
+/// "14-30-05"
+fn is_clock(s: &str) -> bool {
+    s.len() == 8
+        && s.bytes().enumerate().all(|(i, b)| if i == 2 || i == 5 { b == b'-' } else { b.is_ascii_digit() })
+}
+
+/// "2026-02-15", possibly followed by more (a session ID).
+fn starts_with_date(s: &str) -> bool {
+    s.len() >= 10
+        && s.bytes().take(10).enumerate().all(|(i, b)| if i == 4 || i == 7 { b == b'-' } else { b.is_ascii_digit() })
+}
+
+/// The nearest directory named after a date: the session directory.
+fn session_dir(path: &Path) -> Option<&str> {
+    path.ancestors()
+        .filter_map(|p| p.file_name()?.to_str())
+        .find(|name| starts_with_date(name))
+}
+
+/// The date in the session directory's name, else the file's modification
+/// date.
+fn recording_date(path: &Path) -> String {
+    session_dir(path).map(|name| name[..10].to_string()).unwrap_or_else(|| {
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        local_timestamp_at(modified as libc::time_t).0
+    })
+}
+
+/// The session directory's name, else the file's directory's name, kept
+/// to characters that are safe in a file name.
+fn source_name(path: &Path) -> String {
+    let name = session_dir(path)
+        .or_else(|| path.parent()?.file_name()?.to_str())
+        .unwrap_or("unknown");
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
+
+impl Pair {
+    /// Stem of the pair's files in the backfill session.
+    fn name(&self) -> String {
+        format!("{}_{}", self.time, self.source)
+    }
+}
+
+/// Whether the pair already has chunk JSON: next to it or in the `chunks/`
+/// of the session it came from, or in the backfill session's `chunks_dir`.
+fn transcribed(pair: &Pair, chunks_dir: &Path) -> bool {
+    let own = format!("{}.json", pair.time);
+    let dir = pair.system.parent().unwrap_or(Path::new(""));
+    [dir.join(&own), dir.with_file_name("chunks").join(&own), chunks_dir.join(format!("{}.json", pair.name()))]
+        .iter()
+        .any(|p| p.exists())
+}
+
+/// Transcribe `pairs` into one backfill session per day, skipping pairs
+/// that already have chunk JSON, so an interrupted run picks up where it
+/// stopped. The originals are left alone. Returns the sessions written to.
+pub fn transcribe_pairs(
+    pairs: Vec<Pair>,
+    transcribe: TranscribeConfig,
+    config: &BatchConfig,
+) -> Result<Vec<Session>, Box<dyn std::error::Error>> {
+    let mut days: BTreeMap<String, Vec<Pair>> = BTreeMap::new();
+    for pair in pairs {
+        days.entry(pair.date.clone()).or_default().push(pair);
+    }
+
+    let mut todo = Vec::new();
+    let mut skipped = 0;
+    let mut duplicates = 0;
+    for (date, pairs) in days {
+        let session = Session::open_or_create(&config.output_dir, &format!("{date}_backfill"), SessionMeta {
+            title: config.title.clone(),
+            tags: config.tags.clone(),
+            mode: "backfill".to_string(),
+            backend: config.backend.clone(),
+            model: Some(transcribe.model.clone()),
+            source: None,
+        })?;
+        let count = pairs.len();
+        let pairs: Vec<Pair> = pairs.into_iter().filter(|p| !transcribed(p, &session.chunks_dir())).collect();
+        skipped += count - pairs.len();
+
+        // The same session's files found twice (a copy elsewhere) would
+        // overwrite each other
+        let mut names = HashSet::new();
+        let pairs: Vec<Pair> = pairs
+            .into_iter()
+            .filter(|p| {
+                let unique = names.insert(p.name());
+                if !unique {
+                    eprintln!("Skipping {}: same session and time as another pair", p.system.display());
+                    duplicates += 1;
+                }
+                unique
+            })
+            .collect();
+        todo.push((date, session, pairs));
+    }
+
+    let total = todo.iter().map(|(_, _, pairs)| pairs.len()).sum();
+    eprintln!("{total} pairs to transcribe, {skipped} already transcribed");
+    if duplicates > 0 {
+        eprintln!("{duplicates} duplicate pairs skipped");
+    }
+
+    let status = Arc::new(Status::new());
+    let progress = Progress::start(Arc::clone(&status), total);
+    let mut sessions = Vec::new();
+    for (date, mut session, pairs) in todo {
+        if pairs.is_empty() {
+            sessions.push(session);
+            continue;
+        }
+        let transcript_path = session
+            .transcript_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("transcript-{}.md", session.id)));
+        session.transcript_path = Some(transcript_path.clone());
+        // Span the recordings, not when the backfill ran
+        let first = format!("{date}T{}", pairs[0].time.replace('-', ":"));
+        if !session.started_at.starts_with(&date) || first < session.started_at {
+            session.started_at = first;
+        }
+        session.save()?;
+
+        let (tx, rx) = sync_channel(config.concurrency);
+        let handles = pipeline::run(rx, PipelineConfig {
+            transcribe: transcribe.clone(),
+            session: session.clone(),
+            transcript_path: transcript_path.clone(),
+            concurrency: config.concurrency,
+            save_audio: false,
+            audio_format: AudioFormat::Wav,
+            status: Arc::clone(&status),
+            markdown: config.markdown.clone(),
+            results_tx: None,
+        });
+
+        let dir = session.audio_dir();
+        fs::create_dir_all(&dir)?;
+        let done_before = status.done.load(Ordering::SeqCst);
+        let mut last_end = None;
+        for pair in &pairs {
+            let chunk = match pair_chunk(pair, &dir) {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    eprintln!("Skipping {}: {e}", pair.system.display());
+                    status.failed.fetch_add(1, Ordering::SeqCst);
+                    continue;
+                }
+            };
+            last_end = chunk.timestamp_end.clone();
+            status.chunk_queued(0);
+            if tx.send(chunk).is_err() {
+                break;
+            }
+        }
+        drop(tx);
+        pipeline::shutdown(handles);
+
+        if session.jsonl_path().exists() {
+            pipeline::rewrite_markdown(&session, &transcript_path, &config.markdown)?;
+        }
+        // Only a day with something transcribed has an end to record
+        let transcribed = status.done.load(Ordering::SeqCst) > done_before;
+        if let Some(last_end) = last_end.filter(|_| transcribed) {
+            let last = format!("{date}T{}", last_end.replace('-', ":"));
+            if session.ended_at.as_ref().is_none_or(|end| !end.starts_with(&date) || *end < last) {
+                session.ended_at = Some(last);
+            }
+        }
+        session.save()?;
+        sessions.push(session);
+    }
+    progress.finish();
+
+    let failed = status.failed.load(Ordering::SeqCst);
+    if failed > 0 {
+        eprintln!("{failed} pairs failed; run again to retry them");
+    }
+    Ok(sessions)
+}
+
+/// Convert a pair into 16 kHz chunk WAVs in `dir`, leaving the originals.
+fn pair_chunk(pair: &Pair, dir: &Path) -> Result<ChunkSet, Box<dyn std::error::Error>> {
+    let system = load_mono(&pair.system)?;
+    let mic = load_mono(&pair.mic)?;
+    let secs = system.len().max(mic.len()) / TARGET_RATE as usize;
+    let start = pair.time.split('-').filter_map(|n| n.parse::<usize>().ok()).fold(0, |acc, n| acc * 60 + n);
+
+    let mut tracks = Vec::new();
+    for (label, speaker, source, samples) in [
+        ("system", "other", Source::System, &system),
+        ("mic", "you", Source::Mic, &mic),
+    ] {
+        if samples.is_empty() {
+            continue;
+        }
+        tracks.push(Track {
+            label: label.to_string(),
+            speaker: speaker.to_string(),
+            source,
+            path: write_chunk(dir, &pair.name(), label, samples)?,
+            channel: None,
+        });
+    }
+    if tracks.is_empty() {
+        return Err("no audio".into());
+    }
+
+    Ok(ChunkSet {
+        timestamp: pair.time.clone(),
+        timestamp_end: Some(clock(start + secs)),
+        name: Some(pair.name()),
+        tracks,
+        markers: Vec::new(),
+        bytes: 0,
+    })
+}
+
+/// Prints a progress bar each time another chunk finishes.
+struct Progress {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Progress {
+    fn start(status: Arc<Status>, total: usize) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let started = Instant::now();
+                let mut shown = 0;
+                loop {
+                    let stopping = stop.load(Ordering::SeqCst);
+                    let failed = status.failed.load(Ordering::SeqCst);
+                    let finished = status.done.load(Ordering::SeqCst) + failed;
+                    if finished != shown {
+                        shown = finished;
+                        eprintln!("{}", progress_bar(finished, failed, total, started.elapsed()));
+                    }
+                    if stopping {
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(250));
+                }
+            })
+        };
+        Self { stop, handle }
+    }
+
+    fn finish(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.handle.join();
+    }
+}
+
+/// "[#########---------------------] 12/40 pairs, 1 failed, ~6m left"
+fn progress_bar(finished: usize, failed: usize, total: usize, elapsed: Duration) -> String {
+    const WIDTH: usize = 30;
+    let filled = (WIDTH * finished / total.max(1)).min(WIDTH);
+    let mut line = format!("[{}{}] {finished}/{total} pairs", "#".repeat(filled), "-".repeat(WIDTH - filled));
+    if failed > 0 {
+        line.push_str(&format!(", {failed} failed"));
+    }
+    if finished > 0 && finished < total {
+        let left = elapsed.as_secs() as usize * (total - finished) / finished;
+        if left >= 60 {
+            line.push_str(&format!(", ~{}m left", left / 60));
+        } else {
+            line.push_str(&format!(", ~{left}s left"));
+        }
+    }
+    line
+}
+
+/// Seconds as "HH-MM-SS", the form chunk timestamps take.
+fn clock(secs: usize) -> String {
+    format!("{:02}-{:02}-{:02}", secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
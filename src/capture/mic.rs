@@ -1,98 +1,317 @@
-use super::Capture;
+use super::ring::{self, Consumer, Producer};
+use super::{Capture, DeviceEvent, RING_SECONDS};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::sync::mpsc;
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-pub struct MicCapture {
-    stream: cpal::Stream,
-    rx: mpsc::Receiver<Vec<f32>>,
-    sample_rate: u32,
-    channels: u16,
+/// How often to look for the device again after losing it.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// An input device as listed by `scribe devices`.
+pub struct InputDevice {
+    pub name: String,
+    pub is_default: bool,
+    /// Supported configs, e.g. "1ch 8000–48000 Hz F32".
+    pub configs: Vec<String>,
 }
 
-impl MicCapture {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or("No input device found. Check Microphone permission:\n  \
-                     System Settings → Privacy & Security → Microphone")?;
-
-        let device_name = device
-            .description()
-            .map(|d| d.to_string())
-            .unwrap_or_else(|_| "unknown".into());
-        eprintln!("Using input device: {device_name}");
-
-        let supported = device.default_input_config()?;
-        let sample_rate = supported.sample_rate();
-        let channels = supported.channels();
-
-        eprintln!(
-            "  Format: {sample_rate}Hz, {channels}ch, {:?}",
-            supported.sample_format()
-        );
+pub fn input_devices() -> Result<Vec<InputDevice>, Box<dyn std::error::Error>> {
+    let host = cpal::default_host();
+    let default_id = host.default_input_device().and_then(|d| d.id().ok());
+
+    let mut devices = Vec::new();
+    for device in host.input_devices()? {
+        let configs = device
+            .supported_input_configs()
+            .map(|configs| {
+                configs
+                    .map(|c| {
+                        let rates = if c.min_sample_rate() == c.max_sample_rate() {
+                            format!("{} Hz", c.min_sample_rate())
+                        } else {
+                            format!("{}–{} Hz", c.min_sample_rate(), c.max_sample_rate())
+                        };
+                        format!("{}ch {rates} {:?}", c.channels(), c.sample_format())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        devices.push(InputDevice {
+            name: device_name(&device),
+            is_default: default_id.is_some() && device.id().ok() == default_id,
+            configs,
+        });
+    }
+    Ok(devices)
+}
+
+fn device_name(device: &cpal::Device) -> String {
+    device
+        .description()
+        .map(|d| d.name().to_string())
+        .unwrap_or_else(|_| "unknown".into())
+}
+
+/// The input device whose name contains `wanted` (case-insensitive), or
+/// the system default.
+fn find_device(wanted: Option<&str>) -> Result<cpal::Device, Box<dyn std::error::Error>> {
+    let host = cpal::default_host();
+    let Some(wanted) = wanted else {
+        return host.default_input_device().ok_or_else(|| {
+            "No input device found. Check Microphone permission:\n  \
+             System Settings → Privacy & Security → Microphone"
+                .into()
+        });
+    };
+
+    let needle = wanted.to_lowercase();
+    let mut names = Vec::new();
+    for device in host.input_devices()? {
+        let name = device_name(&device);
+        if name.to_lowercase().contains(&needle) {
+            return Ok(device);
+        }
+        names.push(name);
+    }
+    Err(format!("No input device matching '{wanted}'. Available: {}", names.join(", ")).into())
+}
+
+/// Which microphone to capture and in what format. Unset fields keep the
+/// device's defaults.
+#[derive(Clone, Default)]
+pub struct MicConfig {
+    /// Substring of the device name (case-insensitive).
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+/// The device's default config, or the closest supported one to what
+/// `want` asks for: the requested rate, the requested channel count or
+/// else the fewest channels above it, and the default sample format if
+/// there's a choice.
+fn choose_config(
+    device: &cpal::Device,
+    want: &MicConfig,
+) -> Result<cpal::SupportedStreamConfig, Box<dyn std::error::Error>> {
+    let default = device.default_input_config()?;
+    if want.sample_rate.is_none() && want.channels.is_none() {
+        return Ok(default);
+    }
+
+    let rate = want.sample_rate.unwrap_or(default.sample_rate());
+    let channels = want.channels.unwrap_or(default.channels());
+    let mut candidates: Vec<_> = device
+        .supported_input_configs()?
+        .filter(|c| c.min_sample_rate() <= rate && rate <= c.max_sample_rate())
+        .collect();
+    candidates.sort_by_key(|c| {
+        (
+            c.channels() < channels,
+            c.channels() != channels,
+            c.channels(),
+            c.sample_format() != default.sample_format(),
+        )
+    });
+
+    match candidates.into_iter().next() {
+        Some(c) => Ok(c.with_sample_rate(rate)),
+        None => {
+            eprintln!("  {rate}Hz not supported by this device, using its default");
+            Ok(default)
+        }
+    }
+}
+
+/// Input stream delivering `T` samples, converted to f32 on the way into
+/// the ring.
+fn typed_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    producer: Arc<Producer>,
+    err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            producer.push_with(data.len(), |i| cpal::Sample::to_sample::<f32>(data[i]));
+        },
+        err_fn,
+        None,
+    )
+}
 
-        let (tx, rx) = mpsc::channel();
+/// Open an input stream on `device` in `config` feeding `producer`. `lost`
+/// is set if the device goes away.
+fn build_stream(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    producer: &Arc<Producer>,
+    lost: &Arc<AtomicBool>,
+) -> Result<cpal::Stream, Box<dyn std::error::Error>> {
+    eprintln!(
+        "  Format: {}Hz, {}ch, {:?}",
+        config.sample_rate(),
+        config.channels(),
+        config.sample_format()
+    );
 
-        let err_fn = |err: cpal::StreamError| {
+    let err_fn = {
+        let lost = Arc::clone(lost);
+        move |err: cpal::StreamError| {
             eprintln!("Mic stream error: {err}");
-        };
-
-        let stream = match supported.sample_format() {
-            cpal::SampleFormat::F32 => device.build_input_stream(
-                &supported.into(),
-                move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                    let _ = tx.send(data.to_vec());
-                },
-                err_fn,
-                None,
-            )?,
-            cpal::SampleFormat::I16 => {
-                let tx = tx.clone();
-                device.build_input_stream(
-                    &supported.into(),
-                    move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                        let floats: Vec<f32> =
-                            data.iter().map(|&s| s as f32 / 32768.0).collect();
-                        let _ = tx.send(floats);
-                    },
-                    err_fn,
-                    None,
-                )?
+            if matches!(err, cpal::StreamError::DeviceNotAvailable | cpal::StreamError::StreamInvalidated) {
+                lost.store(true, Ordering::SeqCst);
             }
-            format => return Err(format!("Unsupported sample format: {format:?}").into()),
-        };
+        }
+    };
+
+    let producer = Arc::clone(producer);
+    let stream_config = config.config();
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &stream_config,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                producer.push(data);
+            },
+            err_fn,
+            None,
+        )?,
+        cpal::SampleFormat::F64 => typed_stream::<f64>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::I8 => typed_stream::<i8>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::I16 => typed_stream::<i16>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::I24 => typed_stream::<cpal::I24>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::I32 => typed_stream::<i32>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::I64 => typed_stream::<i64>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::U8 => typed_stream::<u8>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::U16 => typed_stream::<u16>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::U24 => typed_stream::<cpal::U24>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::U32 => typed_stream::<u32>(device, &stream_config, producer, err_fn)?,
+        cpal::SampleFormat::U64 => typed_stream::<u64>(device, &stream_config, producer, err_fn)?,
+        format => return Err(format!("Unsupported sample format: {format:?}").into()),
+    };
+
+    Ok(stream)
+}
+
+/// Microphone capture that survives the device going away: the stream is
+/// rebuilt on whatever `config.device` (or the default input) resolves to
+/// once it's back. Only one stream exists at a time, so the shared producer
+/// still has a single writer.
+pub struct MicCapture {
+    stream: RefCell<Option<cpal::Stream>>,
+    ring: Consumer,
+    producer: Arc<Producer>,
+    lost: Arc<AtomicBool>,
+    config: MicConfig,
+    device: RefCell<String>,
+    last_attempt: Cell<Instant>,
+    sample_rate: Cell<u32>,
+    channels: Cell<u16>,
+}
+
+impl MicCapture {
+    pub fn new(config: &MicConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let found = find_device(config.device.as_deref())?;
+        let name = device_name(&found);
+        eprintln!("Using input device: {name}");
+
+        // Sized for the first device; a later one at a higher rate just gets
+        // a shorter ring
+        let supported = choose_config(&found, config)?;
+        let (producer, consumer) = ring::ring(
+            supported.sample_rate() as usize * supported.channels() as usize * RING_SECONDS,
+        );
+        let producer = Arc::new(producer);
+        let lost = Arc::new(AtomicBool::new(false));
+        let stream = build_stream(&found, &supported, &producer, &lost)?;
 
         Ok(Self {
-            stream,
-            rx,
-            sample_rate,
-            channels,
+            stream: RefCell::new(Some(stream)),
+            ring: consumer,
+            producer,
+            lost,
+            config: config.clone(),
+            device: RefCell::new(name),
+            last_attempt: Cell::new(Instant::now()),
+            sample_rate: Cell::new(supported.sample_rate()),
+            channels: Cell::new(supported.channels()),
         })
     }
+
+    fn reconnect(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let device = find_device(self.config.device.as_deref())?;
+        let supported = choose_config(&device, &self.config)?;
+        let stream = build_stream(&device, &supported, &self.producer, &self.lost)?;
+        stream.play()?;
+        *self.stream.borrow_mut() = Some(stream);
+        self.sample_rate.set(supported.sample_rate());
+        self.channels.set(supported.channels());
+        Ok(device_name(&device))
+    }
 }
 
 impl Capture for MicCapture {
     fn sample_rate(&self) -> u32 {
-        self.sample_rate
+        self.sample_rate.get()
     }
 
     fn channels(&self) -> u16 {
-        self.channels
+        self.channels.get()
     }
 
-    fn rx(&self) -> &mpsc::Receiver<Vec<f32>> {
-        &self.rx
+    fn ring(&self) -> &Consumer {
+        &self.ring
     }
 
     fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.stream.play()?;
+        if let Some(stream) = &*self.stream.borrow() {
+            stream.play()?;
+        }
         Ok(())
     }
 
-    #[allow(clippy::unnecessary_wraps)]
     fn stop(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.stream.pause().ok();
+        // A stream whose device went away can't be paused; it's dropped anyway
+        if let Some(stream) = &*self.stream.borrow()
+            && !self.lost.load(Ordering::SeqCst)
+        {
+            stream.pause()?;
+        }
         Ok(())
     }
+
+    fn maintain(&self) -> Option<DeviceEvent> {
+        if self.stream.borrow().is_some() {
+            if !self.lost.swap(false, Ordering::SeqCst) {
+                return None;
+            }
+            // Drop the dead stream; the chunker drains what it left in the
+            // ring before any reconnect can write to it again
+            *self.stream.borrow_mut() = None;
+            self.last_attempt.set(Instant::now());
+            let name = self.device.borrow().clone();
+            eprintln!("Input device lost: {name}. Retrying every {}s", RECONNECT_INTERVAL.as_secs());
+            return Some(DeviceEvent::Lost(name));
+        }
+
+        if self.last_attempt.get().elapsed() < RECONNECT_INTERVAL {
+            return None;
+        }
+        self.last_attempt.set(Instant::now());
+        match self.reconnect() {
+            Ok(name) => {
+                eprintln!("Input device reconnected: {name}");
+                *self.device.borrow_mut() = name.clone();
+                Some(DeviceEvent::Reconnected(name))
+            }
+            Err(_) => None,
+        }
+    }
 }
@@ -1,15 +1,39 @@
 mod mic;
+pub mod ring;
 mod system;
 
-pub use mic::MicCapture;
-pub use system::SystemCapture;
+pub use mic::{input_devices, MicCapture, MicConfig};
+pub use system::{running_apps, SystemCapture, SystemConfig};
 
-use std::sync::mpsc;
+use ring::Consumer;
+
+/// Audio buffered between a capture callback and the chunker. Blocks that
+/// don't fit are dropped and counted rather than stalling the audio thread.
+pub const RING_SECONDS: usize = 10;
+
+/// Where an input's audio comes from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    System,
+    Mic,
+}
+
+/// A change in the capture device, named by the device involved.
+pub enum DeviceEvent {
+    /// The device went away; nothing more arrives until `Reconnected`.
+    Lost(String),
+    /// Capturing again, possibly at a different rate or channel count.
+    Reconnected(String),
+}
 
 pub trait Capture {
     fn sample_rate(&self) -> u32;
     fn channels(&self) -> u16;
-    fn rx(&self) -> &mpsc::Receiver<Vec<f32>>;
+    fn ring(&self) -> &Consumer;
     fn start(&self) -> Result<(), Box<dyn std::error::Error>>;
     fn stop(&self) -> Result<(), Box<dyn std::error::Error>>;
+    /// Called from the capture loop to notice a lost device and reconnect.
+    fn maintain(&self) -> Option<DeviceEvent> {
+        None
+    }
 }
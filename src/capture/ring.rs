@@ -0,0 +1,86 @@
+//! Single-producer, single-consumer ring of samples between a real-time
+//! capture callback and the chunker. The buffer is allocated up front so
+//! the callback never allocates, locks or blocks.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+struct Ring {
+    /// `f32` bits; relaxed atomic loads/stores compile to plain moves.
+    buf: Box<[AtomicU32]>,
+    mask: usize,
+    /// Total samples ever written / read. Only the producer moves `head`,
+    /// only the consumer moves `tail`.
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    overruns: AtomicUsize,
+}
+
+/// Write end, owned by the capture callback.
+pub struct Producer {
+    ring: Arc<Ring>,
+}
+
+/// Read end, polled by the chunker.
+pub struct Consumer {
+    ring: Arc<Ring>,
+}
+
+/// A ring holding at least `capacity` samples (rounded up to a power of two).
+pub fn ring(capacity: usize) -> (Producer, Consumer) {
+    let capacity = capacity.max(1).next_power_of_two();
+    let ring = Arc::new(Ring {
+        buf: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+        mask: capacity - 1,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        overruns: AtomicUsize::new(0),
+    });
+    (Producer { ring: Arc::clone(&ring) }, Consumer { ring })
+}
+
+impl Producer {
+    /// Append a block of samples.
+    pub fn push(&self, samples: &[f32]) -> bool {
+        self.push_with(samples.len(), |i| samples[i])
+    }
+
+    /// Append `len` samples produced by `sample(i)`, for callbacks that
+    /// convert or interleave on the way in. The block goes in whole or not
+    /// at all, so interleaved frames stay aligned; a block that doesn't fit
+    /// counts as an overrun and returns false.
+    pub fn push_with(&self, len: usize, mut sample: impl FnMut(usize) -> f32) -> bool {
+        let ring = &*self.ring;
+        let head = ring.head.load(Ordering::Relaxed);
+        let tail = ring.tail.load(Ordering::Acquire);
+        if ring.buf.len() - (head - tail) < len {
+            ring.overruns.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        for i in 0..len {
+            ring.buf[(head + i) & ring.mask].store(sample(i).to_bits(), Ordering::Relaxed);
+        }
+        ring.head.store(head + len, Ordering::Release);
+        true
+    }
+}
+
+impl Consumer {
+    /// Move everything buffered onto the end of `out`. Returns how many
+    /// samples were read.
+    pub fn read_into(&self, out: &mut Vec<f32>) -> usize {
+        let ring = &*self.ring;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        let head = ring.head.load(Ordering::Acquire);
+        let len = head - tail;
+        out.reserve(len);
+        out.extend((tail..head).map(|i| f32::from_bits(ring.buf[i & ring.mask].load(Ordering::Relaxed))));
+        ring.tail.store(head, Ordering::Release);
+        len
+    }
+
+    /// Blocks dropped because the ring was full.
+    pub fn overruns(&self) -> usize {
+        self.ring.overruns.load(Ordering::Relaxed)
+    }
+}
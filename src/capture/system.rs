@@ -1,12 +1,17 @@
-use super::Capture;
+use super::ring::{self, Consumer, Producer};
+use super::{Capture, RING_SECONDS};
 use screencapturekit::prelude::*;
-use std::sync::mpsc;
 
 const SAMPLE_RATE: u32 = 48000;
 const CHANNELS: u16 = 2;
 
 struct SystemAudioHandler {
-    tx: mpsc::Sender<Vec<f32>>,
+    ring: Producer,
+}
+
+fn read_f32(data: &[u8], i: usize) -> f32 {
+    let b = &data[i * 4..i * 4 + 4];
+    f32::from_le_bytes([b[0], b[1], b[2], b[3]])
 }
 
 impl SCStreamOutputTrait for SystemAudioHandler {
@@ -18,66 +23,131 @@ impl SCStreamOutputTrait for SystemAudioHandler {
             return;
         };
 
-        let num_buffers = audio.num_buffers();
-
-        if num_buffers == 1 {
+        if audio.num_buffers() == 1 {
             if let Some(buf) = audio.get(0) {
-                let samples: Vec<f32> = buf
-                    .data()
-                    .chunks_exact(4)
-                    .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
-                    .collect();
-                let _ = self.tx.send(samples);
+                let data = buf.data();
+                self.ring.push_with(data.len() / 4, |i| read_f32(data, i));
             }
-        } else {
-            let channels: Vec<Vec<f32>> = audio
-                .iter()
-                .map(|buf| {
-                    buf.data()
-                        .chunks_exact(4)
-                        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
-                        .collect()
-                })
-                .collect();
-
-            if let Some(frame_count) = channels.first().map(|c| c.len()) {
-                let mut interleaved = Vec::with_capacity(frame_count * channels.len());
-                for i in 0..frame_count {
-                    for ch in &channels {
-                        interleaved.push(ch.get(i).copied().unwrap_or(0.0));
-                    }
-                }
-                let _ = self.tx.send(interleaved);
+        } else if let (Some(left), Some(right)) = (audio.get(0), audio.get(1)) {
+            // Planar: interleave straight into the ring
+            let (left, right) = (left.data(), right.data());
+            let frames = left.len().min(right.len()) / 4;
+            self.ring.push_with(frames * 2, |i| {
+                let plane = if i % 2 == 0 { left } else { right };
+                read_f32(plane, i / 2)
+            });
+        }
+    }
+}
+
+/// Whose audio to capture. Names match any part of an app's name or bundle
+/// ID, ignoring case. Only apps running when capture starts are matched.
+#[derive(Clone, Default, PartialEq)]
+pub struct SystemConfig {
+    /// Only these apps; every app if empty.
+    pub include: Vec<String>,
+    /// Never these apps.
+    pub exclude: Vec<String>,
+}
+
+/// A running app as listed by `scribe apps`.
+pub struct RunningApp {
+    pub name: String,
+    pub bundle_id: String,
+}
+
+fn shareable_content() -> Result<SCShareableContent, Box<dyn std::error::Error>> {
+    SCShareableContent::get().map_err(|e| {
+        format!(
+            "{e}\n\nEnable Screen Recording:\n  \
+             System Settings → Privacy & Security → Screen & System Audio Recording"
+        )
+        .into()
+    })
+}
+
+pub fn running_apps() -> Result<Vec<RunningApp>, Box<dyn std::error::Error>> {
+    let mut apps: Vec<RunningApp> = shareable_content()?
+        .applications()
+        .iter()
+        .map(|app| RunningApp { name: app.application_name(), bundle_id: app.bundle_identifier() })
+        .filter(|app| !app.name.is_empty())
+        .collect();
+    apps.sort_by_key(|app| app.name.to_lowercase());
+    apps.dedup_by(|a, b| a.bundle_id == b.bundle_id);
+    Ok(apps)
+}
+
+fn matches(app: &SCRunningApplication, patterns: &[String]) -> bool {
+    let name = app.application_name().to_lowercase();
+    let bundle_id = app.bundle_identifier().to_lowercase();
+    patterns.iter().any(|p| {
+        let p = p.to_lowercase();
+        name.contains(&p) || bundle_id.contains(&p)
+    })
+}
+
+/// The display's audio, narrowed to `config`'s apps.
+fn content_filter(
+    content: &SCShareableContent,
+    display: &SCDisplay,
+    config: &SystemConfig,
+) -> Result<SCContentFilter, Box<dyn std::error::Error>> {
+    let apps = content.applications();
+
+    if !config.include.is_empty() {
+        for pattern in &config.include {
+            if !apps.iter().any(|app| matches(app, std::slice::from_ref(pattern))) {
+                return Err(format!("No running app matching '{pattern}'. See `scribe apps`").into());
             }
         }
+        let included: Vec<&SCRunningApplication> = apps
+            .iter()
+            .filter(|app| matches(app, &config.include) && !matches(app, &config.exclude))
+            .collect();
+        let names: Vec<String> = included.iter().map(|app| app.application_name()).collect();
+        eprintln!("Capturing audio from: {}", names.join(", "));
+        return Ok(SCContentFilter::create()
+            .with_display(display)
+            .with_including_applications(&included, &[])
+            .build());
     }
+
+    let excluded: Vec<&SCRunningApplication> = apps.iter().filter(|app| matches(app, &config.exclude)).collect();
+    if excluded.is_empty() {
+        if !config.exclude.is_empty() {
+            eprintln!("No running app matches --exclude-apps, capturing all apps");
+        }
+        return Ok(SCContentFilter::create()
+            .with_display(display)
+            .with_excluding_windows(&[])
+            .build());
+    }
+    let names: Vec<String> = excluded.iter().map(|app| app.application_name()).collect();
+    eprintln!("Excluding audio from: {}", names.join(", "));
+    Ok(SCContentFilter::create()
+        .with_display(display)
+        .with_excluding_applications(&excluded, &[])
+        .build())
 }
 
 pub struct SystemCapture {
     stream: SCStream,
-    rx: mpsc::Receiver<Vec<f32>>,
+    ring: Consumer,
     sample_rate: u32,
     channels: u16,
 }
 
 impl SystemCapture {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let content = SCShareableContent::get().map_err(|e| {
-            format!(
-                "{e}\n\nEnable Screen Recording:\n  \
-                 System Settings → Privacy & Security → Screen & System Audio Recording"
-            )
-        })?;
+    pub fn new(system: &SystemConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = shareable_content()?;
         let display = content
             .displays()
             .into_iter()
             .next()
             .ok_or("No display found")?;
 
-        let filter = SCContentFilter::create()
-            .with_display(&display)
-            .with_excluding_windows(&[])
-            .build();
+        let filter = content_filter(&content, &display, system)?;
 
         let config = SCStreamConfiguration::new()
             .with_width(2)
@@ -87,15 +157,15 @@ impl SystemCapture {
             .with_sample_rate(SAMPLE_RATE as i32)
             .with_channel_count(CHANNELS as i32);
 
-        let (tx, rx) = mpsc::channel();
-        let handler = SystemAudioHandler { tx };
+        let (producer, consumer) = ring::ring(SAMPLE_RATE as usize * CHANNELS as usize * RING_SECONDS);
+        let handler = SystemAudioHandler { ring: producer };
 
         let mut stream = SCStream::new(&filter, &config);
         stream.add_output_handler(handler, SCStreamOutputType::Audio);
 
         Ok(Self {
             stream,
-            rx,
+            ring: consumer,
             sample_rate: SAMPLE_RATE,
             channels: CHANNELS,
         })
@@ -111,8 +181,8 @@ impl Capture for SystemCapture {
         self.channels
     }
 
-    fn rx(&self) -> &mpsc::Receiver<Vec<f32>> {
-        &self.rx
+    fn ring(&self) -> &Consumer {
+        &self.ring
     }
 
     fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
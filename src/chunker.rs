@@ -1,10 +1,13 @@
-use crate::audio;
-use crate::capture::Capture;
+use crate::archive::Archive;
+use crate::audio::{self, AudioFormat};
+use crate::capture::{Capture, DeviceEvent, Source};
+use crate::capture::ring::Consumer;
 use crate::mixer::{self, MixMode};
-use crate::pipeline::ChunkPair;
-use std::path::PathBuf;
+use crate::pipeline::{ChunkSet, Marker, Track};
+use crate::status::Status;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::SyncSender;
 use std::time::{Duration, Instant};
 
 const TARGET_RATE: u32 = 16000;
@@ -12,14 +15,53 @@ const TARGET_RATE: u32 = 16000;
 pub struct ChunkConfig {
     pub chunk_duration: u32,
     pub overlap: u32,
-    pub output_dir: String,
+    pub audio_dir: PathBuf,
+    /// Format for chunk files the pipeline doesn't pick up (it wants WAV).
+    pub format: AudioFormat,
+    /// Continuous session recording (`.wav` or `.flac`), alongside the chunks.
+    pub archive: Option<PathBuf>,
+    /// Chunks waiting for a worker before `backpressure` kicks in.
+    pub max_pending: usize,
+    /// Bytes of untranscribed chunk audio before `backpressure` kicks in.
+    pub max_disk_bytes: Option<u64>,
+    pub backpressure: Backpressure,
+}
+
+/// What to do with a new chunk when the pipeline is over its limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Delete the chunk and leave a gap marker in the transcript.
+    Drop,
+    /// Stop transcribing; keep writing chunks for later `--transcribe-pair`.
+    CaptureOnly,
+    /// Wait for the pipeline. Capture keeps running, so audio that arrives
+    /// while waiting is lost once the capture queues fill (see overruns).
+    Block,
+}
+
+impl Backpressure {
+    pub fn parse(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match name {
+            "drop" => Ok(Self::Drop),
+            "capture-only" => Ok(Self::CaptureOnly),
+            "block" => Ok(Self::Block),
+            other => Err(format!("unknown backpressure policy '{other}' (expected drop, capture-only or block)").into()),
+        }
+    }
 }
 
 /// Returns (date, time) e.g. ("2026-02-15", "14-30-05")
 pub(crate) fn local_timestamp() -> (String, String) {
+    let mut t: libc::time_t = 0;
     unsafe {
-        let mut t: libc::time_t = 0;
         libc::time(&mut t);
+    }
+    local_timestamp_at(t)
+}
+
+/// (date, time) of a Unix time, in the local time zone.
+pub(crate) fn local_timestamp_at(t: libc::time_t) -> (String, String) {
+    unsafe {
         let mut tm: libc::tm = std::mem::zeroed();
         libc::localtime_r(&t, &mut tm);
         let date = format!(
@@ -33,10 +75,9 @@ pub(crate) fn local_timestamp() -> (String, String) {
     }
 }
 
-fn chunk_dir(output_dir: &str, date: &str) -> PathBuf {
-    let dir = PathBuf::from(output_dir).join("audio").join(date);
-    std::fs::create_dir_all(&dir).expect("failed to create chunk output dir");
-    dir
+fn chunk_dir(audio_dir: &Path) -> PathBuf {
+    std::fs::create_dir_all(audio_dir).expect("failed to create chunk output dir");
+    audio_dir.to_path_buf()
 }
 
 fn process_source(buf: &[f32], rate: u32, channels: u16) -> Vec<f32> {
@@ -47,233 +88,538 @@ fn process_source(buf: &[f32], rate: u32, channels: u16) -> Vec<f32> {
     normalized
 }
 
-fn flush_chunk_both(
-    sys_buf: &[f32],
-    mic_buf: &[f32],
-    sys_rate: u32,
-    sys_ch: u16,
-    mic_rate: u32,
-    mic_ch: u16,
+/// A labelled capture feeding one track of each chunk.
+pub struct Input {
+    pub label: String,
+    /// Who the input's transcript is attributed to.
+    pub speaker: String,
+    pub source: Source,
+    pub capture: Box<dyn Capture>,
+}
+
+/// An input's buffered audio, in its capture's current format.
+struct TrackBuf<'a> {
+    input: &'a Input,
+    rate: u32,
+    channels: u16,
+    chunk_samples: usize,
+    overlap_samples: usize,
+    buf: Vec<f32>,
+    /// The device is gone; padded with silence to stay in step.
+    lost: bool,
+    /// The device came back in a different format.
+    reformat: bool,
+}
+
+impl<'a> TrackBuf<'a> {
+    fn new(input: &'a Input, config: &ChunkConfig) -> Self {
+        let mut track = Self {
+            input,
+            rate: 0,
+            channels: 0,
+            chunk_samples: 0,
+            overlap_samples: 0,
+            buf: Vec::new(),
+            lost: false,
+            reformat: false,
+        };
+        track.set_format(config);
+        track
+    }
+
+    /// Pick up the capture's current rate and channel count.
+    fn set_format(&mut self, config: &ChunkConfig) {
+        self.rate = self.input.capture.sample_rate();
+        self.channels = self.input.capture.channels();
+        let frame = (self.rate as usize) * (self.channels as usize);
+        let overlap = config.overlap.min(config.chunk_duration.saturating_sub(1));
+        self.chunk_samples = (config.chunk_duration as usize) * frame;
+        self.overlap_samples = (overlap as usize) * frame;
+        self.reformat = false;
+    }
+
+    fn secs(&self) -> f64 {
+        buffered_secs(self.buf.len(), self.rate, self.channels)
+    }
+}
+
+/// Write the buffered audio of every track: mixed, one file with a
+/// channel per input; split, a mono file per input.
+fn flush_chunk(
+    tracks: &[TrackBuf],
     mix_mode: &MixMode,
-    dir: &PathBuf,
-    chunk_tx: Option<&Sender<ChunkPair>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if sys_buf.is_empty() && mic_buf.is_empty() {
-        return Ok(());
+    dir: &Path,
+    format: AudioFormat,
+    archive: Option<&mut ArchiveFeed>,
+) -> Result<Option<ChunkSet>, Box<dyn std::error::Error>> {
+    if tracks.iter().all(|t| t.buf.is_empty()) {
+        return Ok(None);
     }
 
-    let sys_processed = process_source(sys_buf, sys_rate, sys_ch);
-    let mic_processed = process_source(mic_buf, mic_rate, mic_ch);
+    let processed: Vec<Vec<f32>> = tracks
+        .iter()
+        .map(|t| process_source(&t.buf, t.rate, t.channels))
+        .collect();
 
-    let (date, time) = local_timestamp();
+    let (_, time) = local_timestamp();
+    if let Some(archive) = archive {
+        let sources: Vec<_> = tracks.iter().map(|t| (t.buf.as_slice(), t.rate, t.channels)).collect();
+        archive.chunk(&sources, &time)?;
+    }
 
-    match mix_mode {
+    let track = |t: &TrackBuf, path: PathBuf, channel| Track {
+        label: t.input.label.clone(),
+        speaker: t.input.speaker.clone(),
+        source: t.input.source,
+        path,
+        channel,
+    };
+    let files = match mix_mode {
         MixMode::Stereo => {
-            let stereo = mixer::interleave_stereo(&sys_processed, &mic_processed);
-            let pcm = mixer::f32_to_i16(&stereo);
-            let path = dir.join(format!("{time}.wav"));
-            audio::write_wav_i16(path.to_str().unwrap(), &pcm, TARGET_RATE, 2)?;
+            let channels: Vec<&[f32]> = processed.iter().map(Vec::as_slice).collect();
+            let pcm = mixer::f32_to_i16(&mixer::interleave(&channels));
+            let path = dir.join(format!("{time}.{}", format.extension()));
+            audio::write_audio(&path, &pcm, TARGET_RATE, tracks.len() as u16, format)?;
+            tracks
+                .iter()
+                .enumerate()
+                .map(|(i, t)| track(t, path.clone(), Some(i as u16)))
+                .collect()
         }
         MixMode::Split => {
-            let sys_pcm = mixer::f32_to_i16(&sys_processed);
-            let mic_pcm = mixer::f32_to_i16(&mic_processed);
-            let sys_path = dir.join(format!("{time}_system.wav"));
-            let mic_path = dir.join(format!("{time}_mic.wav"));
-            audio::write_wav_i16(sys_path.to_str().unwrap(), &sys_pcm, TARGET_RATE, 1)?;
-            audio::write_wav_i16(mic_path.to_str().unwrap(), &mic_pcm, TARGET_RATE, 1)?;
-
-            if let Some(tx) = chunk_tx {
-                let _ = tx.send(ChunkPair {
-                    timestamp: time,
-                    date,
-                    system_path: sys_path,
-                    mic_path: mic_path,
-                });
+            let mut files = Vec::with_capacity(tracks.len());
+            for (t, samples) in tracks.iter().zip(&processed) {
+                let path = dir.join(format!("{time}_{}.{}", t.input.label, format.extension()));
+                audio::write_audio(&path, &mixer::f32_to_i16(samples), TARGET_RATE, 1, format)?;
+                files.push(track(t, path, None));
             }
+            files
         }
-    }
+    };
 
-    Ok(())
+    Ok(Some(ChunkSet {
+        timestamp: time,
+        timestamp_end: None,
+        name: None,
+        tracks: files,
+        markers: Vec::new(),
+        bytes: 0,
+    }))
 }
 
-fn flush_chunk_single(
-    buf: &[f32],
-    rate: u32,
-    channels: u16,
-    dir: &PathBuf,
-    label: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if buf.is_empty() {
-        return Ok(());
+/// Feeds the session archive from the chunk buffers, one channel per
+/// source. `done` counts samples at the front of each buffer (the retained
+/// overlap) that are already in the archive.
+struct ArchiveFeed {
+    archive: Archive,
+    done: Vec<usize>,
+}
+
+impl ArchiveFeed {
+    fn open(config: &ChunkConfig, channels: usize) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let Some(path) = &config.archive else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            archive: Archive::create(path, TARGET_RATE, channels as u16)?,
+            done: vec![0; channels],
+        }))
     }
 
-    let processed = process_source(buf, rate, channels);
-    let pcm = mixer::f32_to_i16(&processed);
+    /// Archive what's new in each `(buffer, rate, channels)` source and cue
+    /// where the chunk labelled `label` starts.
+    fn chunk(&mut self, sources: &[(&[f32], u32, u16)], label: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut tracks: Vec<Vec<f32>> = sources
+            .iter()
+            .zip(&self.done)
+            .map(|(&(buf, rate, ch), &done)| {
+                let mono = mixer::to_mono(&buf[done.min(buf.len())..], ch);
+                mixer::resample(&mono, rate, TARGET_RATE)
+            })
+            .collect();
+
+        let overlap = sources
+            .iter()
+            .zip(&self.done)
+            .map(|(&(_, rate, ch), &done)| (buffered_secs(done, rate, ch) * TARGET_RATE as f64) as u64)
+            .max()
+            .unwrap_or(0);
+        self.archive.cue(self.archive.frames().saturating_sub(overlap), label)?;
+
+        // Pad to the longest track so channels stay aligned at chunk boundaries
+        let len = tracks.iter().map(Vec::len).max().unwrap_or(0);
+        for t in &mut tracks {
+            t.resize(len, 0.0);
+        }
+        let interleaved: Vec<f32> = (0..len).flat_map(|i| tracks.iter().map(move |t| t[i])).collect();
+        self.archive.append(&mixer::f32_to_i16(&interleaved))
+    }
 
-    let (_, time) = local_timestamp();
-    let filename = if label.is_empty() {
-        format!("{time}.wav")
-    } else {
-        format!("{time}_{label}.wav")
-    };
-    let path = dir.join(filename);
-    audio::write_wav_i16(path.to_str().unwrap(), &pcm, TARGET_RATE, 1)?;
+    /// The buffers now hold only `lens` samples, all already archived.
+    fn retain(&mut self, lens: &[usize]) {
+        self.done.copy_from_slice(lens);
+    }
+}
 
-    Ok(())
+fn buffered_secs(len: usize, rate: u32, channels: u16) -> f64 {
+    len as f64 / (rate as f64 * channels as f64)
 }
 
-pub fn run_chunked_both(
-    system: &dyn Capture,
-    mic: &dyn Capture,
-    mix_mode: &MixMode,
-    config: &ChunkConfig,
-    running: &AtomicBool,
-    chunk_tx: Option<&Sender<ChunkPair>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let sys_rx = system.rx();
-    let mic_rx = mic.rx();
-    let sys_rate = system.sample_rate();
-    let sys_ch = system.channels();
-    let mic_rate = mic.sample_rate();
-    let mic_ch = mic.channels();
-
-    let overlap = config.overlap.min(config.chunk_duration.saturating_sub(1));
-    let sys_chunk_samples = (config.chunk_duration as usize) * (sys_rate as usize) * (sys_ch as usize);
-    let mic_chunk_samples = (config.chunk_duration as usize) * (mic_rate as usize) * (mic_ch as usize);
-    let sys_overlap_samples = (overlap as usize) * (sys_rate as usize) * (sys_ch as usize);
-    let mic_overlap_samples = (overlap as usize) * (mic_rate as usize) * (mic_ch as usize);
-
-    let (date, _) = local_timestamp();
-    let dir = chunk_dir(&config.output_dir, &date);
-
-    let mut sys_buf: Vec<f32> = Vec::new();
-    let mut mic_buf: Vec<f32> = Vec::new();
-    let mut chunk_start = Instant::now();
-    let mut last_report = Instant::now();
-    let mut chunk_count: u32 = 0;
+/// Hands chunks to the pipeline, applying the backpressure policy when it
+/// falls behind. `tx` is dropped when the session goes capture-only.
+struct Handoff<'a> {
+    tx: Option<&'a SyncSender<ChunkSet>>,
+    max_pending: usize,
+    max_disk_bytes: Option<u64>,
+    policy: Backpressure,
+    /// Cleared on stop, which gives up waiting on a blocked pipeline.
+    running: &'a AtomicBool,
+    /// Start of the run of dropped chunks not yet marked in the transcript.
+    dropped_since: Option<String>,
+}
 
-    while running.load(Ordering::SeqCst) {
-        let mut got_data = false;
+impl<'a> Handoff<'a> {
+    fn new(tx: Option<&'a SyncSender<ChunkSet>>, config: &ChunkConfig, running: &'a AtomicBool) -> Self {
+        Self {
+            tx,
+            max_pending: config.max_pending,
+            max_disk_bytes: config.max_disk_bytes,
+            policy: config.backpressure,
+            running,
+            dropped_since: None,
+        }
+    }
+
+    /// Whether chunks still go to the pipeline (which wants WAV).
+    fn live(&self) -> bool {
+        self.tx.is_some()
+    }
 
-        while let Ok(chunk) = sys_rx.try_recv() {
-            sys_buf.extend(chunk);
-            got_data = true;
+    /// Which limit taking on `bytes` more would break, if any.
+    fn over_limit(&self, status: &Status, bytes: u64) -> Option<&'static str> {
+        if status.queued.load(Ordering::SeqCst) >= self.max_pending {
+            return Some("pending chunks");
         }
-        while let Ok(chunk) = mic_rx.try_recv() {
-            mic_buf.extend(chunk);
-            got_data = true;
+        let pending = status.pending_bytes.load(Ordering::SeqCst);
+        if self.max_disk_bytes.is_some_and(|max| pending > 0 && pending + bytes > max) {
+            return Some("disk");
         }
+        None
+    }
 
-        if !got_data {
-            std::thread::sleep(Duration::from_millis(2));
+    /// Hand a chunk to the pipeline along with any pending markers,
+    /// anchored in a chunk that ended at `chunk_end` and lasted `duration`
+    /// seconds.
+    fn send(&mut self, chunk: Option<ChunkSet>, status: &Status, chunk_end: Instant, duration: f64) {
+        let (Some(mut chunk), Some(tx)) = (chunk, self.tx) else {
+            return;
+        };
+        chunk.bytes = chunk
+            .files()
+            .into_iter()
+            .filter_map(|p| std::fs::metadata(p).ok())
+            .map(|m| m.len())
+            .sum();
+
+        if let Some(limit) = self.over_limit(status, chunk.bytes) {
+            match self.policy {
+                Backpressure::Drop => {
+                    eprintln!("Transcription backlog ({limit} limit): dropped chunk {}", chunk.timestamp);
+                    for file in chunk.files() {
+                        let _ = std::fs::remove_file(file);
+                    }
+                    status.dropped.fetch_add(1, Ordering::SeqCst);
+                    self.dropped_since.get_or_insert(chunk.timestamp);
+                    return;
+                }
+                Backpressure::CaptureOnly => {
+                    eprintln!("Transcription backlog ({limit} limit): capture only from chunk {}", chunk.timestamp);
+                    self.capture_only(chunk, status);
+                    return;
+                }
+                Backpressure::Block => {
+                    eprintln!("Transcription backlog ({limit} limit): waiting for workers");
+                    while self.over_limit(status, chunk.bytes).is_some() {
+                        if !self.running.load(Ordering::SeqCst) {
+                            eprintln!("Stopping with workers still busy: chunk {} not transcribed", chunk.timestamp);
+                            self.capture_only(chunk, status);
+                            return;
+                        }
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                }
+            }
         }
 
-        // Check if chunk is ready (use sample count as primary, time as fallback)
-        let chunk_ready = sys_buf.len() >= sys_chunk_samples || mic_buf.len() >= mic_chunk_samples;
-
-        if chunk_ready {
-            flush_chunk_both(
-                &sys_buf, &mic_buf,
-                sys_rate, sys_ch, mic_rate, mic_ch,
-                mix_mode, &dir, chunk_tx,
-            )?;
-            chunk_count += 1;
+        if let Some(from) = self.dropped_since.take() {
+            status.push_marker(Marker::Untranscribed { from, to: Some(chunk.timestamp.clone()) });
+        }
+        let chunk_start = chunk_end
+            .checked_sub(Duration::from_secs_f64(duration))
+            .unwrap_or(chunk_end);
+        chunk.markers = status
+            .take_markers()
+            .into_iter()
+            .map(|(at, m)| m.place(at, chunk_start, duration))
+            .collect();
+        let bytes = chunk.bytes;
+        status.chunk_queued(bytes);
+        if tx.send(chunk).is_err() {
+            status.chunk_unqueued(bytes);
+        }
+    }
 
-            // Retain overlap
-            let sys_drain = sys_buf.len().saturating_sub(sys_overlap_samples);
-            sys_buf.drain(..sys_drain);
-            let mic_drain = mic_buf.len().saturating_sub(mic_overlap_samples);
-            mic_buf.drain(..mic_drain);
+    /// Stop sending chunks; this one and any after it stay on disk.
+    fn capture_only(&mut self, chunk: ChunkSet, status: &Status) {
+        eprintln!(
+            "  audio stays in {}",
+            chunk.tracks[0].path.parent().map(|d| d.display().to_string()).unwrap_or_default()
+        );
+        self.tx = None;
+        status.capture_only.store(true, Ordering::SeqCst);
+        let from = self.dropped_since.take().unwrap_or(chunk.timestamp);
+        status.push_marker(Marker::Untranscribed { from, to: None });
+    }
 
-            chunk_start = Instant::now();
+    /// Mark a run of drops that lasted to the end of the session.
+    fn finish(&mut self, status: &Status) {
+        if let Some(from) = self.dropped_since.take() {
+            let (_, to) = local_timestamp();
+            status.push_marker(Marker::Untranscribed { from, to: Some(to) });
         }
+    }
+}
 
-        if last_report.elapsed() >= Duration::from_secs(5) {
-            let chunk_elapsed = chunk_start.elapsed().as_secs_f32();
-            eprintln!("  chunks: {chunk_count}, current chunk: {chunk_elapsed:.1}s");
-            last_report = Instant::now();
+/// Follows the shared pause flag and records a marker for each pause.
+struct PauseTracker {
+    since: Option<String>,
+}
+
+impl PauseTracker {
+    fn new() -> Self {
+        Self { since: None }
+    }
+
+    /// Returns true on the transition into pause, when the caller should
+    /// flush whatever it has buffered.
+    fn update(&mut self, paused: bool, status: &Status) -> bool {
+        match (self.since.is_some(), paused) {
+            (false, true) => {
+                let (_, time) = local_timestamp();
+                eprintln!("Paused at {}", time.replace('-', ":"));
+                self.since = Some(time);
+                true
+            }
+            (true, false) => {
+                self.close(status);
+                eprintln!("Resumed");
+                false
+            }
+            _ => false,
         }
     }
 
-    // Final drain from channels
-    while let Ok(chunk) = sys_rx.try_recv() {
-        sys_buf.extend(chunk);
+    /// End an open pause (on resume, or when capture stops while paused).
+    fn close(&mut self, status: &Status) {
+        if let Some(from) = self.since.take() {
+            let (_, to) = local_timestamp();
+            status.push_marker(Marker::Paused { from, to });
+        }
     }
-    while let Ok(chunk) = mic_rx.try_recv() {
-        mic_buf.extend(chunk);
+}
+
+/// Move what `ring` has buffered onto `buf`, feeding the `label` meter.
+/// While paused the audio only feeds the meter. Returns samples read.
+fn drain(ring: &Consumer, buf: &mut Vec<f32>, label: &str, paused: bool, status: &Status) -> usize {
+    let start = buf.len();
+    let n = ring.read_into(buf);
+    status.update_level(label, &buf[start..]);
+    if paused {
+        buf.truncate(start);
     }
+    n
+}
 
-    // Flush final partial chunk
-    flush_chunk_both(
-        &sys_buf, &mic_buf,
-        sys_rate, sys_ch, mic_rate, mic_ch,
-        mix_mode, &dir, chunk_tx,
-    )?;
-    if !sys_buf.is_empty() || !mic_buf.is_empty() {
-        chunk_count += 1;
+/// Record a device change on the `input` input in the transcript.
+/// `fallback` says what capture carries on with while the device is gone.
+fn device_marker(event: &DeviceEvent, input: &str, fallback: &str, status: &Status) {
+    let (_, time) = local_timestamp();
+    let message = match event {
+        DeviceEvent::Lost(name) => format!("{name} disconnected{fallback}"),
+        DeviceEvent::Reconnected(name) => format!("reconnected: {name}"),
+    };
+    status.push_marker(Marker::Device { time, offset: 0.0, input: input.to_string(), message });
+}
+
+/// What's still recording, e.g. ", recording system audio only".
+fn fallback(tracks: &[TrackBuf]) -> String {
+    let live: Vec<&str> = tracks
+        .iter()
+        .filter(|t| !t.lost)
+        .map(|t| match t.input.label.as_str() {
+            "system" => "system audio",
+            label => label,
+        })
+        .collect();
+    if live.is_empty() {
+        String::new()
+    } else {
+        format!(", recording {} only", live.join(", "))
     }
+}
 
-    eprintln!("Total chunks: {chunk_count}");
+/// Write out the chunk buffered in `tracks` and hand it to the pipeline.
+fn cut(
+    tracks: &[TrackBuf],
+    mix_mode: &MixMode,
+    dir: &Path,
+    config: &ChunkConfig,
+    handoff: &mut Handoff,
+    archive: Option<&mut ArchiveFeed>,
+    status: &Status,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_end = Instant::now();
+    let duration = tracks.iter().map(TrackBuf::secs).fold(0.0, f64::max);
+    // The pipeline reads WAVs and archives them in `config.format` itself
+    let format = if handoff.live() { AudioFormat::Wav } else { config.format };
+    let chunk = flush_chunk(tracks, mix_mode, dir, format, archive)?;
+    handoff.send(chunk, status, chunk_end, duration);
+    if tracks.iter().any(|t| !t.buf.is_empty()) {
+        status.chunk_flushed();
+    }
     Ok(())
 }
 
-pub fn run_chunked_single(
-    capture: &dyn Capture,
-    label: &str,
+/// How long to sleep when the rings are empty; a few callbacks' worth.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn report(status: &Status) {
+    let chunks = status.chunks_flushed.load(Ordering::SeqCst);
+    let chunk_elapsed = status.current_chunk_secs();
+    let paused = if status.is_paused() { " (paused)" } else { "" };
+    let mut backlog = String::new();
+    let dropped = status.dropped.load(Ordering::SeqCst);
+    if dropped > 0 {
+        backlog.push_str(&format!(", dropped: {dropped}"));
+    }
+    let overruns = status.overruns.load(Ordering::SeqCst);
+    if overruns > 0 {
+        backlog.push_str(&format!(", overruns: {overruns}"));
+    }
+    if status.capture_only.load(Ordering::SeqCst) {
+        backlog.push_str(" (capture only)");
+    }
+    eprintln!("  chunks: {chunks}, current chunk: {chunk_elapsed:.1}s{paused}{backlog}");
+}
+
+/// Chunk several inputs in step, one track each.
+pub fn run_chunked(
+    inputs: &[Input],
+    mix_mode: &MixMode,
     config: &ChunkConfig,
     running: &AtomicBool,
+    status: &Status,
+    chunk_tx: Option<&SyncSender<ChunkSet>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let rx = capture.rx();
-    let rate = capture.sample_rate();
-    let channels = capture.channels();
-
-    let overlap = config.overlap.min(config.chunk_duration.saturating_sub(1));
-    let chunk_samples = (config.chunk_duration as usize) * (rate as usize) * (channels as usize);
-    let overlap_samples = (overlap as usize) * (rate as usize) * (channels as usize);
+    let mut tracks: Vec<TrackBuf> = inputs.iter().map(|input| TrackBuf::new(input, config)).collect();
 
-    let (date, _) = local_timestamp();
-    let dir = chunk_dir(&config.output_dir, &date);
+    let dir = chunk_dir(&config.audio_dir);
+    let mut handoff = Handoff::new(chunk_tx, config, running);
+    let mut archive = ArchiveFeed::open(config, tracks.len())?;
 
-    let mut buf: Vec<f32> = Vec::new();
-    let mut chunk_start = Instant::now();
     let mut last_report = Instant::now();
-    let mut chunk_count: u32 = 0;
+    let mut pause = PauseTracker::new();
 
     while running.load(Ordering::SeqCst) {
-        match rx.recv_timeout(Duration::from_millis(100)) {
-            Ok(samples) => buf.extend(samples),
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
-            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+        let paused = status.is_paused();
+
+        let mut reformat = false;
+        for i in 0..tracks.len() {
+            let Some(event) = tracks[i].input.capture.maintain() else { continue };
+            tracks[i].lost = matches!(event, DeviceEvent::Lost(_));
+            device_marker(&event, &tracks[i].input.label, &fallback(&tracks), status);
+            let t = &mut tracks[i];
+            t.reformat = (t.input.capture.sample_rate(), t.input.capture.channels()) != (t.rate, t.channels);
+            reformat |= t.reformat;
+        }
+
+        if pause.update(paused, status) || reformat {
+            // Cut the chunk at the pause point; nothing carries over. After
+            // a format change that input's ring already holds the new
+            // device's audio
+            for t in tracks.iter_mut().filter(|t| !t.reformat) {
+                t.input.capture.ring().read_into(&mut t.buf);
+            }
+            cut(&tracks, mix_mode, &dir, config, &mut handoff, archive.as_mut(), status)?;
+            for t in &mut tracks {
+                t.buf.clear();
+            }
+            if let Some(a) = &mut archive {
+                a.retain(&vec![0; tracks.len()]);
+            }
         }
 
-        if buf.len() >= chunk_samples {
-            flush_chunk_single(&buf, rate, channels, &dir, label)?;
-            chunk_count += 1;
+        for t in tracks.iter_mut().filter(|t| t.reformat) {
+            t.set_format(config);
+        }
+
+        let mut read = 0;
+        for t in &mut tracks {
+            read += drain(t.input.capture.ring(), &mut t.buf, &t.input.label, paused, status);
+        }
+        if read == 0 {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        // Pad lost inputs with silence so they're still in step with the
+        // others when the device comes back
+        let secs = tracks.iter().filter(|t| !t.lost).map(TrackBuf::secs).fold(0.0, f64::max);
+        for t in tracks.iter_mut().filter(|t| t.lost) {
+            let target = (secs * t.rate as f64) as usize * t.channels as usize;
+            if t.buf.len() < target {
+                t.buf.resize(target, 0.0);
+            }
+        }
 
-            let drain = buf.len().saturating_sub(overlap_samples);
-            buf.drain(..drain);
+        // Check if chunk is ready (use sample count as primary, time as fallback)
+        if tracks.iter().any(|t| t.buf.len() >= t.chunk_samples) {
+            cut(&tracks, mix_mode, &dir, config, &mut handoff, archive.as_mut(), status)?;
 
-            chunk_start = Instant::now();
+            // Retain overlap
+            for t in &mut tracks {
+                let drain = t.buf.len().saturating_sub(t.overlap_samples);
+                t.buf.drain(..drain);
+            }
+            if let Some(a) = &mut archive {
+                a.retain(&tracks.iter().map(|t| t.buf.len()).collect::<Vec<_>>());
+            }
         }
 
         if last_report.elapsed() >= Duration::from_secs(5) {
-            let chunk_elapsed = chunk_start.elapsed().as_secs_f32();
-            eprintln!("  chunks: {chunk_count}, current chunk: {chunk_elapsed:.1}s");
+            let overruns = inputs.iter().map(|i| i.capture.ring().overruns()).sum();
+            status.overruns.store(overruns, Ordering::SeqCst);
+            report(status);
             last_report = Instant::now();
         }
     }
 
-    // Final drain
-    while let Ok(samples) = rx.try_recv() {
-        buf.extend(samples);
+    pause.close(status);
+
+    // Final drain from the rings
+    if !status.is_paused() {
+        for t in &mut tracks {
+            t.input.capture.ring().read_into(&mut t.buf);
+        }
     }
 
     // Flush final partial chunk
-    if !buf.is_empty() {
-        flush_chunk_single(&buf, rate, channels, &dir, label)?;
-        chunk_count += 1;
+    cut(&tracks, mix_mode, &dir, config, &mut handoff, archive.as_mut(), status)?;
+    handoff.finish(status);
+
+    if let Some(feed) = archive {
+        feed.archive.finish()?;
     }
 
-    eprintln!("Total chunks: {chunk_count}");
+    eprintln!("Total chunks: {}", status.chunks_flushed.load(Ordering::SeqCst));
     Ok(())
 }
@@ -0,0 +1,219 @@
+use crate::session::Session;
+use crate::status::Status;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const REQUEST_NONE: u8 = 0;
+const REQUEST_PAUSE: u8 = 1;
+const REQUEST_RESUME: u8 = 2;
+
+/// Set from the signal handler, applied to `Status` by a watcher thread
+/// (signal handlers can't safely touch anything behind an `Arc`).
+static PAUSE_REQUEST: AtomicU8 = AtomicU8::new(REQUEST_NONE);
+
+extern "C" fn on_signal(sig: libc::c_int) {
+    let request = if sig == libc::SIGUSR1 { REQUEST_PAUSE } else { REQUEST_RESUME };
+    PAUSE_REQUEST.store(request, Ordering::SeqCst);
+}
+
+/// `kill -USR1 <pid>` pauses capture, `kill -USR2 <pid>` resumes it.
+pub fn install_signals(status: Arc<Status>) {
+    unsafe {
+        libc::signal(libc::SIGUSR1, on_signal as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGUSR2, on_signal as *const () as libc::sighandler_t);
+    }
+
+    thread::spawn(move || loop {
+        match PAUSE_REQUEST.swap(REQUEST_NONE, Ordering::SeqCst) {
+            REQUEST_PAUSE => status.pause(),
+            REQUEST_RESUME => status.resume(),
+            _ => {}
+        }
+        thread::sleep(Duration::from_millis(100));
+    });
+}
+
+/// Control socket for the session recording into `output_dir`.
+pub fn socket_path(output_dir: &str) -> PathBuf {
+    PathBuf::from(output_dir).join("scribe.sock")
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+struct Context {
+    session_id: String,
+    title: Option<String>,
+    status: Arc<Status>,
+    running: Arc<AtomicBool>,
+}
+
+/// JSON-RPC 2.0 server on a Unix socket, one request per line.
+/// The socket file is removed when the server is dropped.
+pub struct ControlServer {
+    path: PathBuf,
+}
+
+impl ControlServer {
+    pub fn start(
+        path: PathBuf,
+        session: &Session,
+        status: Arc<Status>,
+        running: Arc<AtomicBool>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(format!("another scribe is listening on {}", path.display()).into());
+            }
+            fs::remove_file(&path)?; // stale, left by a crash
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        let ctx = Arc::new(Context {
+            session_id: session.id.clone(),
+            title: session.meta.title.clone(),
+            status,
+            running,
+        });
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let ctx = Arc::clone(&ctx);
+                thread::spawn(move || {
+                    if let Err(e) = serve(stream, &ctx) {
+                        eprintln!("control connection error: {e}");
+                    }
+                });
+            }
+        });
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn serve(stream: UnixStream, ctx: &Context) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(req) => match dispatch(&req, ctx) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": req.id, "result": result }),
+                Err((code, message)) => json!({
+                    "jsonrpc": "2.0",
+                    "id": req.id,
+                    "error": { "code": code, "message": message },
+                }),
+            },
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32700, "message": format!("parse error: {e}") },
+            }),
+        };
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+fn dispatch(req: &Request, ctx: &Context) -> Result<Value, (i32, String)> {
+    let status = &ctx.status;
+    match req.method.as_str() {
+        "status" => {
+            let meters: Vec<Value> = status
+                .meters()
+                .iter()
+                .map(|m| json!({ "label": m.label, "rms": m.rms, "peak": m.peak }))
+                .collect();
+            Ok(json!({
+                "session_id": ctx.session_id,
+                "title": ctx.title,
+                "pid": std::process::id(),
+                "running": ctx.running.load(Ordering::SeqCst),
+                "paused": status.is_paused(),
+                "elapsed_seconds": status.elapsed_secs(),
+                "chunks_flushed": status.chunks_flushed.load(Ordering::SeqCst),
+                "current_chunk_seconds": status.current_chunk_secs(),
+                "queued": status.queued.load(Ordering::SeqCst),
+                "in_flight": status.in_flight.load(Ordering::SeqCst),
+                "done": status.done.load(Ordering::SeqCst),
+                "failed": status.failed.load(Ordering::SeqCst),
+                "pending_bytes": status.pending_bytes.load(Ordering::SeqCst),
+                "dropped": status.dropped.load(Ordering::SeqCst),
+                "overruns": status.overruns.load(Ordering::SeqCst),
+                "capture_only": status.capture_only.load(Ordering::SeqCst),
+                "bookmarks": status.bookmarks().len(),
+                "meters": meters,
+            }))
+        }
+        "pause" => {
+            status.pause();
+            Ok(json!({ "paused": true }))
+        }
+        "resume" => {
+            status.resume();
+            Ok(json!({ "paused": false }))
+        }
+        "bookmark" => {
+            let note = match req.params.get("note") {
+                None | Some(Value::Null) => None,
+                Some(v) => Some(v.as_str().ok_or((-32602, "note must be a string".to_string()))?.to_string()),
+            };
+            let time = status.add_bookmark(note);
+            Ok(json!({ "time": time }))
+        }
+        "stop" => {
+            ctx.running.store(false, Ordering::SeqCst);
+            Ok(json!({ "stopping": true }))
+        }
+        "tail" => {
+            let n = match req.params.get("lines") {
+                None => 20,
+                Some(v) => v.as_u64().ok_or((-32602, "lines must be a number".to_string()))? as usize,
+            };
+            Ok(json!({ "lines": status.recent_transcript(n) }))
+        }
+        other => Err((-32601, format!("unknown method: {other}"))),
+    }
+}
+
+/// Send one request to a running instance and return its `result`.
+pub fn call(path: &Path, method: &str, params: Value) -> Result<Value, Box<dyn std::error::Error>> {
+    let mut stream = UnixStream::connect(path)
+        .map_err(|_| format!("scribe is not running (no control socket at {})", path.display()))?;
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(stream, "{request}")?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let mut response: Value = serde_json::from_str(&line)?;
+
+    if let Some(err) = response.get("error") {
+        let message = err.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+        return Err(message.to_string().into());
+    }
+    Ok(response["result"].take())
+}
@@ -0,0 +1,244 @@
+use crate::mixer::{self, StreamResampler};
+use audiopus::coder::Decoder as OpusDecoder;
+use audiopus::packet::Packet as OpusPacket;
+use audiopus::{Channels, MutSignals, SampleRate};
+use ogg::reading::PacketReader;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CODEC_TYPE_NULL, DecoderOptions};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// What a recording holds, read from its headers.
+pub struct Info {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// When the container states it.
+    pub duration_secs: Option<f64>,
+}
+
+type Reader = (Box<dyn FormatReader>, u32);
+
+/// Open `path` and find its first audio track.
+fn open(path: &Path) -> Result<Reader, Box<dyn std::error::Error>> {
+    let file = File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("{}: unsupported format ({e})", path.display()))?;
+    let format = probed.format;
+
+    let track_id = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| format!("{}: no audio track", path.display()))?
+        .id;
+    Ok((format, track_id))
+}
+
+/// Ogg/Opus is decoded with the `audiopus` bindings; symphonia has no Opus
+/// decoder.
+const OPUS_RATE: u32 = 48_000;
+
+/// Largest Opus frame: 120 ms at 48 kHz.
+const OPUS_MAX_FRAME: usize = 5760;
+
+/// Whether `path` is an Ogg stream carrying Opus, as `--audio-format=opus`
+/// writes. The ID header is the first packet, so it's in the first page.
+fn is_opus(path: &Path) -> bool {
+    let mut start = Vec::with_capacity(128);
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    if file.take(128).read_to_end(&mut start).is_err() {
+        return false;
+    }
+    start.starts_with(b"OggS") && start.windows(8).any(|w| w == b"OpusHead")
+}
+
+/// Channel count and pre-skip from an Opus ID header.
+fn opus_head(data: &[u8], path: &Path) -> Result<(u16, u64), Box<dyn std::error::Error>> {
+    if data.len() < 19 || !data.starts_with(b"OpusHead") {
+        return Err(format!("{}: missing Opus header", path.display()).into());
+    }
+    let channels = data[9] as u16;
+    if !(1..=2).contains(&channels) {
+        return Err(format!("{}: {channels}-channel Opus isn't supported", path.display()).into());
+    }
+    Ok((channels, u16::from_le_bytes([data[10], data[11]]) as u64))
+}
+
+/// A reader past the Opus headers, with the channel count and pre-skip.
+type OpusReader = (PacketReader<BufReader<File>>, u16, u64);
+
+fn opus_reader(path: &Path) -> Result<OpusReader, Box<dyn std::error::Error>> {
+    let file = File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut reader = PacketReader::new(BufReader::new(file));
+    let head = reader.read_packet()?.ok_or_else(|| format!("{}: empty Ogg stream", path.display()))?;
+    let (channels, pre_skip) = opus_head(&head.data, path)?;
+    // OpusTags
+    reader.read_packet()?;
+    Ok((reader, channels, pre_skip))
+}
+
+/// `decode_packets` for Ogg/Opus: the pre-skip is dropped and the last
+/// page's granule position trims the end padding.
+fn decode_opus_packets(
+    path: &Path,
+    mut on_packet: impl FnMut(&[f32], u32, u16) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut reader, channels, pre_skip) = opus_reader(path)?;
+    let ch = channels as usize;
+    let mut decoder = OpusDecoder::new(SampleRate::Hz48000, if ch == 1 { Channels::Mono } else { Channels::Stereo })?;
+
+    let mut pcm = vec![0f32; OPUS_MAX_FRAME * ch];
+    // Samples per channel decoded so far, pre-skip included
+    let mut position = 0u64;
+    let mut decoded_any = false;
+    while let Some(packet) = reader.read_packet()? {
+        let frames = match OpusPacket::try_from(packet.data.as_slice())
+            .and_then(|input| decoder.decode_float(Some(input), MutSignals::try_from(&mut pcm[..])?, false))
+        {
+            Ok(frames) => frames as u64,
+            Err(e) => {
+                eprintln!("  skipping undecodable packet: {e}");
+                continue;
+            }
+        };
+
+        let start = pre_skip.saturating_sub(position).min(frames);
+        let mut end = frames;
+        if packet.last_in_stream() {
+            end = end.min(packet.absgp_page().saturating_sub(position));
+        }
+        position += frames;
+        if start < end {
+            decoded_any = true;
+            on_packet(&pcm[start as usize * ch..end as usize * ch], OPUS_RATE, channels)?;
+        }
+    }
+
+    if !decoded_any {
+        return Err(format!("{}: no audio decoded", path.display()).into());
+    }
+    Ok(())
+}
+
+/// Check that `path` can be decoded, without decoding it.
+pub fn probe(path: &Path) -> Result<Info, Box<dyn std::error::Error>> {
+    if is_opus(path) {
+        let (_, channels, _) = opus_reader(path)?;
+        return Ok(Info { sample_rate: OPUS_RATE, channels, duration_secs: None });
+    }
+    let (format, track_id) = open(path)?;
+    let params = &format.tracks().iter().find(|t| t.id == track_id).unwrap().codec_params;
+    symphonia::default::get_codecs()
+        .make(params, &DecoderOptions::default())
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    let sample_rate = params.sample_rate.unwrap_or(0);
+    Ok(Info {
+        sample_rate,
+        channels: params.channels.map_or(0, |c| c.count() as u16),
+        duration_secs: params
+            .n_frames
+            .filter(|_| sample_rate > 0)
+            .map(|n| n as f64 / sample_rate as f64),
+    })
+}
+
+/// Decode the first audio track of a WAV, FLAC, MP3, M4A/AAC, Ogg Vorbis
+/// or Ogg Opus file a packet at a time, handing each packet's interleaved
+/// samples to `on_packet` with their rate and channel count. Packets that
+/// fail to decode are skipped.
+pub fn decode_packets(
+    path: &Path,
+    mut on_packet: impl FnMut(&[f32], u32, u16) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if is_opus(path) {
+        return decode_opus_packets(path, on_packet);
+    }
+    let (mut format, track_id) = open(path)?;
+    let params = &format.tracks().iter().find(|t| t.id == track_id).unwrap().codec_params;
+    let mut decoder = symphonia::default::get_codecs().make(params, &DecoderOptions::default())?;
+
+    let mut decoded_any = false;
+    let mut buf: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(DecodeError::DecodeError(e)) => {
+                eprintln!("  skipping undecodable packet: {e}");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        let needed = decoded.capacity() * spec.channels.count();
+        if buf.as_ref().is_none_or(|b| b.capacity() < needed) {
+            buf = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buf = buf.as_mut().unwrap();
+        buf.copy_interleaved_ref(decoded);
+        decoded_any = true;
+        on_packet(buf.samples(), spec.rate, spec.channels.count() as u16)?;
+    }
+
+    if !decoded_any {
+        return Err(format!("{}: no audio decoded", path.display()).into());
+    }
+    Ok(())
+}
+
+/// Decode `path` to mono at `rate` Hz, handing the audio to `on_samples` as
+/// it's decoded so a long recording is never held whole. Returns the
+/// duration in seconds.
+pub fn decode_mono(
+    path: &Path,
+    rate: u32,
+    mut on_samples: impl FnMut(&[f32]) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<f64, Box<dyn std::error::Error>> {
+    let mut resampler: Option<(u32, StreamResampler)> = None;
+    let mut total = 0;
+    decode_packets(path, |samples, from_rate, channels| {
+        if resampler.as_ref().is_none_or(|(r, _)| *r != from_rate) {
+            // Rate changed mid-stream: flush what the old one holds
+            if let Some((_, old)) = resampler.take() {
+                let tail = old.finish();
+                total += tail.len();
+                on_samples(&tail)?;
+            }
+            resampler = Some((from_rate, StreamResampler::new(from_rate, rate)));
+        }
+        let (_, resampler) = resampler.as_mut().unwrap();
+        let out = resampler.process(&mixer::to_mono(samples, channels));
+        total += out.len();
+        on_samples(&out)
+    })?;
+    if let Some((_, resampler)) = resampler {
+        let tail = resampler.finish();
+        total += tail.len();
+        on_samples(&tail)?;
+    }
+    Ok(total as f64 / rate as f64)
+}
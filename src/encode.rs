@@ -0,0 +1,496 @@
+//! In-memory encoders for 16-bit PCM: FLAC (lossless) and Ogg/Opus (speech).
+
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+
+const FLAC_BLOCK_SIZE: usize = 4096;
+const MAX_PARTITION_ORDER: u32 = 6;
+
+/// Opus voice bitrate per channel — transcribes as well as lossless at 16 kHz.
+const OPUS_BITRATE: i32 = 24_000;
+const OPUS_FRAME_MS: usize = 20;
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bytes: Vec::new(), acc: 0, bits: 0 }
+    }
+
+    fn put(&mut self, value: u64, n: u32) {
+        for i in (0..n).rev() {
+            self.acc = (self.acc << 1) | ((value >> i) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.bytes.push(self.acc as u8);
+                self.acc = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn put_signed(&mut self, value: i64, n: u32) {
+        self.put(value as u64 & ((1u64 << n) - 1), n);
+    }
+
+    fn put_unary(&mut self, zeros: u64) {
+        for _ in 0..zeros {
+            self.put(0, 1);
+        }
+        self.put(1, 1);
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.put(0, 8 - self.bits);
+        }
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &b| {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &b| {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+/// FLAC's UTF-8-style variable-length frame number.
+fn put_utf8(w: &mut BitWriter, n: u64) {
+    if n < 0x80 {
+        w.put(n, 8);
+        return;
+    }
+    let mut cont = Vec::new();
+    let mut v = n;
+    let mut lead_bits = 6; // payload bits available in the first byte
+    while v >= (1 << lead_bits) {
+        cont.push(0x80 | (v & 0x3f));
+        v >>= 6;
+        lead_bits -= 1;
+    }
+    let len = cont.len() as u32 + 1;
+    let marker = (0xffu64 << (8 - len)) & 0xff;
+    w.put(marker | v, 8);
+    for b in cont.into_iter().rev() {
+        w.put(b, 8);
+    }
+}
+
+/// Residual of the fixed polynomial predictor of `order`.
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<i64> {
+    (order..samples.len())
+        .map(|i| {
+            let s = |k: usize| samples[i - k];
+            match order {
+                0 => s(0),
+                1 => s(0) - s(1),
+                2 => s(0) - 2 * s(1) + s(2),
+                3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+                _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+            }
+        })
+        .collect()
+}
+
+fn zigzag(r: i64) -> u64 {
+    ((r << 1) ^ (r >> 63)) as u64
+}
+
+/// Rice parameter and its cost in bits for a run of residuals. The optimum
+/// sits near log2 of the mean, so only its neighbours are tried.
+fn rice_cost(residual: &[i64]) -> (u32, u64) {
+    let cost = |k: u32| residual.iter().map(|&r| (zigzag(r) >> k) + 1 + k as u64).sum::<u64>();
+    let mean = residual.iter().map(|&r| zigzag(r)).sum::<u64>() / residual.len().max(1) as u64;
+    let guess = (64 - mean.leading_zeros()).min(14);
+    [guess.saturating_sub(1), guess, (guess + 1).min(14)]
+        .into_iter()
+        .map(|k| (k, cost(k)))
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((0, 0))
+}
+
+/// Partition order, per-partition parameters and total bits for a residual.
+fn plan_residual(residual: &[i64], block: usize, order: usize) -> (u32, Vec<u32>, u64) {
+    let mut best: Option<(u32, Vec<u32>, u64)> = None;
+    for p in 0..=MAX_PARTITION_ORDER {
+        let parts = 1usize << p;
+        if !block.is_multiple_of(parts) || block / parts <= order {
+            break;
+        }
+        let per = block / parts;
+        let mut params = Vec::with_capacity(parts);
+        let mut bits = 0;
+        let mut start = 0;
+        for i in 0..parts {
+            let len = if i == 0 { per - order } else { per };
+            let (k, cost) = rice_cost(&residual[start..start + len]);
+            params.push(k);
+            bits += cost + 4;
+            start += len;
+        }
+        if best.as_ref().is_none_or(|b| bits < b.2) {
+            best = Some((p, params, bits));
+        }
+    }
+    best.unwrap_or((0, vec![0], 0))
+}
+
+fn write_subframe(w: &mut BitWriter, samples: &[i64], bps: u32) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        w.put(0, 1);
+        w.put(0b000000, 6);
+        w.put(0, 1);
+        w.put_signed(samples[0], bps);
+        return;
+    }
+
+    let block = samples.len();
+    let max_order = 4.min(block.saturating_sub(1));
+    let (order, residual, (part_order, params, bits)) = (0..=max_order)
+        .map(|order| {
+            let residual = fixed_residual(samples, order);
+            let plan = plan_residual(&residual, block, order);
+            (order, residual, plan)
+        })
+        .min_by_key(|(order, _, plan)| plan.2 + (*order as u64) * bps as u64)
+        .unwrap();
+
+    // Noise doesn't predict; store it as-is rather than grow it
+    if bits + (order as u64) * bps as u64 >= (block as u64) * bps as u64 {
+        w.put(0, 1);
+        w.put(0b000001, 6);
+        w.put(0, 1);
+        for &s in samples {
+            w.put_signed(s, bps);
+        }
+        return;
+    }
+
+    w.put(0, 1);
+    w.put(0b001000 | order as u64, 6);
+    w.put(0, 1);
+    for &s in &samples[..order] {
+        w.put_signed(s, bps);
+    }
+
+    w.put(0b00, 2); // Rice, 4-bit parameters
+    w.put(part_order as u64, 4);
+    let per = block >> part_order;
+    let mut start = 0;
+    for (i, &k) in params.iter().enumerate() {
+        let len = if i == 0 { per - order } else { per };
+        w.put(k as u64, 4);
+        for &r in &residual[start..start + len] {
+            let u = zigzag(r);
+            w.put_unary(u >> k);
+            w.put(u & ((1u64 << k) - 1), k);
+        }
+        start += len;
+    }
+}
+
+/// Streaming FLAC encoder. Each full block is written as soon as it's
+/// buffered, so a stream cut short still decodes up to the last block.
+/// STREAMINFO says "length unknown" until `finish` fills it in.
+pub struct FlacWriter<W: Write + Seek> {
+    out: W,
+    sample_rate: u32,
+    channels: usize,
+    pending: Vec<i16>,
+    frames: u64,
+    frame_number: u64,
+}
+
+/// Byte offset of the STREAMINFO field holding rate, channels, depth and length.
+const STREAMINFO_TOTALS_OFFSET: u64 = 18;
+
+impl<W: Write + Seek> FlacWriter<W> {
+    pub fn new(mut out: W, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        let mut w = BitWriter::new();
+        w.bytes.extend_from_slice(b"fLaC");
+        // STREAMINFO, the only (and so last) metadata block
+        w.put(1, 1);
+        w.put(0, 7);
+        w.put(34, 24);
+        w.put(FLAC_BLOCK_SIZE as u64, 16);
+        w.put(FLAC_BLOCK_SIZE as u64, 16);
+        w.put(0, 24); // min/max frame size unknown
+        w.put(0, 24);
+        w.put(streaminfo_totals(sample_rate, channels, 0), 64);
+        w.put(0, 64); // MD5 not computed
+        w.put(0, 64);
+        out.write_all(&w.bytes)?;
+
+        Ok(Self {
+            out,
+            sample_rate,
+            channels: channels as usize,
+            pending: Vec::with_capacity(FLAC_BLOCK_SIZE * channels as usize),
+            frames: 0,
+            frame_number: 0,
+        })
+    }
+
+    /// Append interleaved samples.
+    pub fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.pending.extend_from_slice(samples);
+        let block_len = FLAC_BLOCK_SIZE * self.channels;
+        while self.pending.len() >= block_len {
+            let block: Vec<i16> = self.pending.drain(..block_len).collect();
+            self.write_frame(&block)?;
+        }
+        Ok(())
+    }
+
+    fn write_frame(&mut self, samples: &[i16]) -> io::Result<()> {
+        let ch = self.channels;
+        let block = samples.len() / ch;
+        let mut f = BitWriter::new();
+        f.put(0b11111111111110, 14);
+        f.put(0, 1);
+        f.put(0, 1); // fixed block size
+        f.put(0b0111, 4); // block size in 16 bits after the header
+        f.put(0b0000, 4); // sample rate from STREAMINFO
+        f.put(ch as u64 - 1, 4); // independent channels
+        f.put(0b100, 3); // 16 bits per sample
+        f.put(0, 1);
+        put_utf8(&mut f, self.frame_number);
+        f.put(block as u64 - 1, 16);
+        let crc = crc8(&f.bytes);
+        f.put(crc as u64, 8);
+
+        for c in 0..ch {
+            let channel: Vec<i64> = (0..block).map(|i| samples[i * ch + c] as i64).collect();
+            write_subframe(&mut f, &channel, 16);
+        }
+        f.align();
+        let crc = crc16(&f.bytes);
+        f.put(crc as u64, 16);
+
+        self.out.write_all(&f.bytes)?;
+        self.frames += block as u64;
+        self.frame_number += 1;
+        Ok(())
+    }
+
+    /// Write the final short block, record the length and hand back the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.pending.is_empty() {
+            let rest = std::mem::take(&mut self.pending);
+            self.write_frame(&rest)?;
+        }
+        let totals = streaminfo_totals(self.sample_rate, self.channels as u16, self.frames);
+        let end = self.out.stream_position()?;
+        self.out.seek(SeekFrom::Start(STREAMINFO_TOTALS_OFFSET))?;
+        self.out.write_all(&totals.to_be_bytes())?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Packed rate (20 bits), channels-1 (3), bits-1 (5) and total frames (36).
+fn streaminfo_totals(sample_rate: u32, channels: u16, frames: u64) -> u64 {
+    ((sample_rate as u64) << 44) | ((channels as u64 - 1) << 41) | (15 << 36) | (frames & 0xf_ffff_ffff)
+}
+
+/// Encode interleaved 16-bit PCM as a FLAC stream.
+pub fn flac(samples: &[i16], sample_rate: u32, channels: u16) -> Vec<u8> {
+    let encode = || -> io::Result<Vec<u8>> {
+        let mut writer = FlacWriter::new(Cursor::new(Vec::new()), sample_rate, channels)?;
+        writer.write(samples)?;
+        Ok(writer.finish()?.into_inner())
+    };
+    encode().expect("writing to memory can't fail")
+}
+
+/// Encode interleaved 16-bit PCM (8/12/16/24/48 kHz, mono or stereo) as Ogg/Opus.
+pub fn opus(
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if channels > 2 {
+        return Err(format!("Opus output is mono or stereo, not {channels} channels; use flac or wav").into());
+    }
+    let rate = SampleRate::try_from(sample_rate as i32)?;
+    let opus_channels = if channels == 1 { Channels::Mono } else { Channels::Stereo };
+    let mut encoder = Encoder::new(rate, opus_channels, Application::Voip)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(OPUS_BITRATE * channels as i32))?;
+
+    // Ogg/Opus granule positions always count 48 kHz samples
+    let scale = 48_000 / sample_rate as u64;
+    let pre_skip = encoder.lookahead()? as u64 * scale;
+
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1);
+    head.push(channels as u8);
+    head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    head.extend_from_slice(&sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+
+    let vendor = concat!("scribe ", env!("CARGO_PKG_VERSION"));
+    let mut tags = Vec::new();
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+
+    let serial = 1;
+    let mut writer = PacketWriter::new(Vec::new());
+    writer.write_packet(head.into_boxed_slice(), serial, PacketWriteEndInfo::EndPage, 0)?;
+    writer.write_packet(tags.into_boxed_slice(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+    let ch = channels as usize;
+    let frame = sample_rate as usize * OPUS_FRAME_MS / 1000;
+    let total = samples.len() / ch;
+    // Keep encoding past the end until the lookahead delay is flushed out
+    let needed = total + (pre_skip / scale) as usize;
+    let mut packet = vec![0u8; 4000];
+    let mut pcm = vec![0i16; frame * ch];
+    let mut pos = 0;
+
+    while pos < needed || pos == 0 {
+        // Past the end only silence is fed, to flush the lookahead
+        let start = pos.min(total);
+        let take = frame.min(total - start);
+        pcm.fill(0);
+        pcm[..take * ch].copy_from_slice(&samples[start * ch..(start + take) * ch]);
+        pos += frame;
+
+        let len = encoder.encode(&pcm, &mut packet)?;
+        let (end, granule) = if pos >= needed {
+            // The final granule position trims the padding on decode
+            (PacketWriteEndInfo::EndStream, pre_skip + total as u64 * scale)
+        } else {
+            (PacketWriteEndInfo::NormalPacket, pos as u64 * scale)
+        };
+        writer.write_packet(packet[..len].to_vec().into_boxed_slice(), serial, end, granule)?;
+    }
+
+    Ok(writer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode;
+    use std::path::PathBuf;
+
+    /// Write `data` to a scratch file and decode it back to interleaved
+    /// samples, with the rate and channel count the decoder reported.
+    fn round_trip(name: &str, data: &[u8]) -> (Vec<f32>, u32, u16) {
+        let path: PathBuf = std::env::temp_dir().join(format!("scribe-test-{}-{name}", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let mut samples = Vec::new();
+        let mut format = (0, 0);
+        let result = decode::decode_packets(&path, |packet, rate, channels| {
+            samples.extend_from_slice(packet);
+            format = (rate, channels);
+            Ok(())
+        });
+        std::fs::remove_file(&path).ok();
+        result.unwrap();
+        (samples, format.0, format.1)
+    }
+
+    /// A 440 Hz tone over deterministic noise.
+    fn signal(frames: usize, channels: u16, rate: u32) -> Vec<i16> {
+        let mut seed = 0x2545_f491_u32;
+        (0..frames * channels as usize)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let t = (i / channels as usize) as f32 / rate as f32;
+                let tone = (t * 440.0 * std::f32::consts::TAU).sin() * 12_000.0;
+                let noise = (seed >> 16) as i16 as f32 / 16.0;
+                (tone + noise) as i16
+            })
+            .collect()
+    }
+
+    fn assert_flac_exact(name: &str, samples: &[i16], rate: u32, channels: u16) {
+        let (decoded, decoded_rate, decoded_channels) = round_trip(name, &flac(samples, rate, channels));
+        assert_eq!((decoded_rate, decoded_channels), (rate, channels));
+        let decoded: Vec<i16> = decoded.iter().map(|&s| (s * 32768.0).round() as i16).collect();
+        assert_eq!(decoded.len(), samples.len());
+        assert!(decoded == samples, "{name}: FLAC round trip isn't lossless");
+    }
+
+    #[test]
+    fn flac_round_trip_is_lossless() {
+        // Lengths off the block size leave a short final block
+        assert_flac_exact("mono.flac", &signal(FLAC_BLOCK_SIZE * 3 + 123, 1, 16_000), 16_000, 1);
+        assert_flac_exact("stereo.flac", &signal(FLAC_BLOCK_SIZE * 2 + 7, 2, 48_000), 48_000, 2);
+        assert_flac_exact("silence.flac", &vec![0; 5000], 16_000, 1);
+        let extremes: Vec<i16> = (0..9000).map(|i| if i % 3 == 0 { i16::MIN } else { i16::MAX }).collect();
+        assert_flac_exact("extremes.flac", &extremes, 16_000, 1);
+    }
+
+    #[test]
+    fn flac_writer_streams_in_pieces() {
+        let samples = signal(FLAC_BLOCK_SIZE * 2 + 500, 2, 48_000);
+        let mut writer = FlacWriter::new(Cursor::new(Vec::new()), 48_000, 2).unwrap();
+        for piece in samples.chunks(1000) {
+            writer.write(piece).unwrap();
+        }
+        let data = writer.finish().unwrap().into_inner();
+        assert_eq!(data, flac(&samples, 48_000, 2));
+    }
+
+    /// Normalized correlation of two equal-length signals.
+    fn correlation(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    #[test]
+    fn opus_round_trip() {
+        // 47 900 ends inside the lookahead after the last full frame
+        for (frames, channels) in [(48_311, 1), (48_311, 2), (47_900, 1), (47_900, 2)] {
+            let samples = signal(frames, channels, 48_000);
+            let data = opus(&samples, 48_000, channels).unwrap();
+            let (decoded, rate, decoded_channels) = round_trip(&format!("{frames}-{channels}.opus"), &data);
+            assert_eq!((rate, decoded_channels), (48_000, channels));
+            // Pre-skip and the final granule position trim it to the input length
+            assert_eq!(decoded.len(), samples.len());
+            let original: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+            assert!(correlation(&original, &decoded) > 0.9);
+        }
+    }
+
+    #[test]
+    fn opus_short_tail_at_16k() {
+        // 300 samples: the second frame, flushing the lookahead, starts past the end
+        let samples = signal(300, 1, 16_000);
+        let (decoded, rate, _) = round_trip("tail-16k.opus", &opus(&samples, 16_000, 1).unwrap());
+        assert_eq!(rate, 48_000);
+        assert_eq!(decoded.len(), samples.len() * 3);
+    }
+
+    #[test]
+    fn opus_rejects_surround() {
+        assert!(opus(&[0; 600], 48_000, 6).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::transcribe::{DroppedSegment, Segment, Transcript};
+
+/// Whisper's own silence test: likely no speech and not confident either.
+const NO_SPEECH_PROB: f64 = 0.6;
+const NO_SPEECH_LOGPROB: f64 = -1.0;
+
+/// Text that gzips this well is almost certainly a loop.
+const MAX_COMPRESSION_RATIO: f64 = 2.4;
+
+/// Consecutive repeats of one n-gram (n = 1..=4) that count as a loop.
+const MAX_REPEATS: usize = 4;
+
+/// Share of a segment's words a loop must cover for the segment to be
+/// dropped; "no, no, no, no, we can't ship Friday" is real speech.
+const MIN_LOOP_SHARE: f64 = 0.75;
+
+/// Shorter repeats ("yeah", "no, no") are often real.
+const MIN_DUPLICATE_WORDS: usize = 4;
+
+/// Phrases Whisper produces from silence and noise, matched against the
+/// whole segment after normalization. Most are subtitle credits that made
+/// it into the training data. People do say these, so they only count when
+/// the model also doubts there was speech.
+const BLOCKLIST: &[&str] = &[
+    "thank you for watching",
+    "thanks for watching",
+    "thank you for watching and please subscribe",
+    "thank you so much for watching",
+    "thanks for watching and see you next time",
+    "please subscribe",
+    "please like and subscribe",
+    "like and subscribe",
+    "dont forget to like and subscribe",
+    "subscribe to my channel",
+    "see you in the next video",
+    "subtitles by the amaraorg community",
+    "subtitles by",
+    "transcription by castingwords",
+];
+
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// True if some 1–4 word sequence repeats back to back `MAX_REPEATS` times
+/// and those repeats make up most of the text.
+fn is_repetitive(text: &str) -> bool {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let min_covered = (words.len() as f64 * MIN_LOOP_SHARE).ceil() as usize;
+    for n in 1..=4 {
+        let mut i = 0;
+        while i + n <= words.len() {
+            let mut repeats = 1;
+            while i + (repeats + 1) * n <= words.len()
+                && words[i..i + n] == words[i + repeats * n..i + (repeats + 1) * n]
+            {
+                repeats += 1;
+            }
+            if repeats >= MAX_REPEATS && repeats * n >= min_covered {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+fn reason(seg: &Segment, norm: &str, previous: Option<&str>) -> Option<&'static str> {
+    if norm.is_empty() {
+        return None;
+    }
+    if let (Some(p), Some(lp)) = (seg.no_speech_prob, seg.avg_logprob)
+        && p > NO_SPEECH_PROB
+        && lp < NO_SPEECH_LOGPROB
+    {
+        return Some("no_speech");
+    }
+    if seg.compression_ratio.is_some_and(|r| r > MAX_COMPRESSION_RATIO) {
+        return Some("compression_ratio");
+    }
+    if is_repetitive(norm) {
+        return Some("repetition");
+    }
+    if previous == Some(norm) && norm.split_whitespace().count() >= MIN_DUPLICATE_WORDS {
+        return Some("duplicate");
+    }
+    let doubtful = seg.no_speech_prob.is_some_and(|p| p > NO_SPEECH_PROB)
+        || seg.avg_logprob.is_some_and(|lp| lp < NO_SPEECH_LOGPROB);
+    if doubtful && BLOCKLIST.contains(&norm) {
+        return Some("blocklist");
+    }
+    None
+}
+
+/// Drop likely hallucinated segments (and their words) from `transcript`,
+/// recording each in `transcript.dropped`.
+pub fn filter(transcript: &mut Transcript) {
+    let mut previous: Option<String> = None;
+    let mut kept = Vec::with_capacity(transcript.segments.len());
+
+    for seg in std::mem::take(&mut transcript.segments) {
+        let norm = normalize(&seg.text);
+        match reason(&seg, &norm, previous.as_deref()) {
+            Some(reason) => transcript.dropped.push(DroppedSegment {
+                speaker: String::new(),
+                start: seg.start,
+                end: seg.end,
+                text: seg.text,
+                reason: reason.to_string(),
+            }),
+            None => {
+                if !norm.is_empty() {
+                    previous = Some(norm);
+                }
+                kept.push(seg);
+            }
+        }
+    }
+    transcript.segments = kept;
+
+    if transcript.dropped.is_empty() {
+        return;
+    }
+    let dropped = &transcript.dropped;
+    transcript
+        .words
+        .retain(|w| !dropped.iter().any(|d| w.start >= d.start && w.end <= d.end));
+    transcript.text = transcript
+        .segments
+        .iter()
+        .map(|s| s.text.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, no_speech_prob: f64, avg_logprob: f64) -> Segment {
+        Segment {
+            start: 0.0,
+            end: 2.0,
+            text: text.to_string(),
+            words: Vec::new(),
+            no_speech_prob: Some(no_speech_prob),
+            avg_logprob: Some(avg_logprob),
+            compression_ratio: None,
+        }
+    }
+
+    fn check(seg: &Segment, previous: Option<&str>) -> Option<&'static str> {
+        reason(seg, &normalize(&seg.text), previous)
+    }
+
+    #[test]
+    fn no_speech() {
+        assert_eq!(check(&segment("Hmm.", 0.9, -1.5), None), Some("no_speech"));
+        assert_eq!(check(&segment("Hmm.", 0.9, -0.2), None), None);
+    }
+
+    #[test]
+    fn compression_ratio() {
+        let seg = Segment { compression_ratio: Some(3.0), ..segment("la la la", 0.0, -0.2) };
+        assert_eq!(check(&seg, None), Some("compression_ratio"));
+    }
+
+    #[test]
+    fn repetition() {
+        let seg = segment("I think I think I think I think I think", 0.0, -0.2);
+        assert_eq!(check(&seg, None), Some("repetition"));
+    }
+
+    #[test]
+    fn short_loop_inside_real_speech_is_kept() {
+        let seg = segment("No, no, no, no, we can't ship Friday.", 0.0, -0.2);
+        assert_eq!(check(&seg, None), None);
+    }
+
+    #[test]
+    fn duplicate() {
+        let seg = segment("We should ship on Friday.", 0.0, -0.2);
+        assert_eq!(check(&seg, Some("we should ship on friday")), Some("duplicate"));
+        let short = segment("Yeah, sure.", 0.0, -0.2);
+        assert_eq!(check(&short, Some("yeah sure")), None);
+    }
+
+    #[test]
+    fn blocklist_needs_doubt() {
+        assert_eq!(check(&segment("Thanks for watching!", 0.7, -0.3), None), Some("blocklist"));
+        assert_eq!(check(&segment("Thanks for watching!", 0.1, -1.2), None), Some("blocklist"));
+        assert_eq!(check(&segment("Thanks for watching!", 0.1, -0.3), None), None);
+    }
+
+    #[test]
+    fn bare_you_is_kept() {
+        assert_eq!(check(&segment("You.", 0.7, -0.3), None), None);
+    }
+
+    #[test]
+    fn filter_drops_segment_and_its_words() {
+        let word = |w: &str, start: f64| crate::transcribe::Word {
+            word: w.to_string(),
+            start,
+            end: start + 0.5,
+            probability: None,
+        };
+        let mut kept = segment("Hello there.", 0.0, -0.2);
+        kept.end = 1.0;
+        let mut dropped = segment("Thanks for watching!", 0.8, -0.5);
+        dropped.start = 1.0;
+        let mut transcript = Transcript {
+            text: String::new(),
+            language: None,
+            segments: vec![kept, dropped],
+            words: vec![word("Hello", 0.0), word("Thanks", 1.0)],
+            duration: 2.0,
+            dropped: Vec::new(),
+        };
+        filter(&mut transcript);
+        assert_eq!(transcript.text, "Hello there.");
+        assert_eq!(transcript.words.len(), 1);
+        assert_eq!(transcript.dropped[0].reason, "blocklist");
+    }
+}
@@ -67,6 +67,9 @@ impl LocalServer {
                 .arg(&model_path)
                 .arg("--port")
                 .arg(port.to_string())
+                // Default is English; detect unless a request sets `language`
+                .arg("-l")
+                .arg("auto")
                 .stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .pre_exec(|| {
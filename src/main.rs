@@ -1,57 +1,188 @@
+mod archive;
 mod audio;
+mod batch;
 mod capture;
 mod chunker;
+mod control;
+mod decode;
+mod encode;
+mod hallucination;
 mod local;
 mod mixer;
 mod pipeline;
+mod session;
+mod status;
+mod summary;
+mod template;
 mod transcribe;
+mod tui;
+mod vad;
+mod vocab;
 
-use capture::{Capture, MicCapture, SystemCapture};
-use chunker::ChunkConfig;
+use audio::AudioFormat;
+use capture::{Capture, MicCapture, MicConfig, Source, SystemCapture, SystemConfig};
+use chunker::{Backpressure, ChunkConfig};
 use mixer::MixMode;
-use std::path::PathBuf;
+use session::{Session, SessionMeta};
+use status::Status;
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 const TARGET_RATE: u32 = 16000;
 
 enum CaptureMode {
     System,
     Mic,
-    Both(MixMode),
+    /// `None` picks split files when transcribing, one mixed file otherwise.
+    Both(Option<MixMode>),
+}
+
+/// A labelled input recorded to its own track, from `--input=LABEL=SOURCE`.
+#[derive(Clone, PartialEq)]
+struct InputSpec {
+    label: String,
+    speaker: String,
+    source: Source,
+    /// Microphone name substring, instead of `--device`.
+    device: Option<String>,
+    /// Apps to capture system audio from, instead of `--apps`.
+    apps: Vec<String>,
+}
+
+/// System audio as "other" and the mic as "you".
+fn default_inputs() -> Vec<InputSpec> {
+    vec![
+        InputSpec { label: "system".into(), speaker: "other".into(), source: Source::System, device: None, apps: Vec::new() },
+        InputSpec { label: "mic".into(), speaker: "you".into(), source: Source::Mic, device: None, apps: Vec::new() },
+    ]
+}
+
+/// "LABEL=system", "LABEL=system:APP,APP", "LABEL=mic" or
+/// "LABEL=mic:DEVICE". The label names the track's files, so it can't
+/// contain path separators.
+fn parse_input(value: &str) -> Option<InputSpec> {
+    let (label, source) = value.split_once('=')?;
+    if label.is_empty() || label.contains(['/', '\\']) {
+        return None;
+    }
+    let (source, device, apps) = match source {
+        "system" => (Source::System, None, Vec::new()),
+        "mic" => (Source::Mic, None, Vec::new()),
+        other => match other.split_once(':')? {
+            ("system", apps) => (Source::System, None, comma_list(apps)),
+            ("mic", device) => (Source::Mic, Some(device.to_string()), Vec::new()),
+            _ => return None,
+        },
+    };
+    Some(InputSpec { label: label.to_string(), speaker: label.to_string(), source, device, apps })
+}
+
+/// "a, b,,c" → ["a", "b", "c"]
+fn comma_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
 }
 
 struct Config {
     mode: CaptureMode,
+    /// Tracks recorded by chunked capture.
+    inputs: Vec<InputSpec>,
+    system: SystemConfig,
+    mic: MicConfig,
     chunk_duration: u32,
     overlap: u32,
     output_dir: String,
     output: Option<String>,
     no_transcribe: bool,
     save_audio: bool,
+    audio_format: AudioFormat,
+    archive: Option<AudioFormat>,
     concurrency: usize,
+    max_pending: usize,
+    max_disk_bytes: Option<u64>,
+    backpressure: Backpressure,
     use_local: bool,
     local_port: Option<u16>,
+    title: Option<String>,
+    tags: Vec<String>,
+    tui: bool,
+    summarize: bool,
+    rolling_summary: usize,
+    low_confidence: Option<f64>,
+    templates: template::Templates,
 }
 
-fn parse_config() -> Config {
+fn parse_config() -> Result<Config, Box<dyn std::error::Error>> {
     let args: Vec<String> = std::env::args().collect();
 
-    let mode = if args.iter().any(|a| a == "--system") {
+    let mut inputs: Vec<InputSpec> = Vec::new();
+    for value in args.iter().filter_map(|a| a.strip_prefix("--input=")) {
+        match parse_input(value) {
+            Some(spec) if inputs.iter().any(|i| i.label == spec.label) => {
+                eprintln!("Ignoring --input={value}: label already used");
+            }
+            Some(spec) => inputs.push(spec),
+            None => eprintln!("Ignoring --input={value}: expected LABEL=system[:APPS] or LABEL=mic[:DEVICE]"),
+        }
+    }
+
+    let mode = if inputs.is_empty() && args.iter().any(|a| a == "--system") {
         CaptureMode::System
-    } else if args.iter().any(|a| a == "--mic") {
+    } else if inputs.is_empty() && args.iter().any(|a| a == "--mic") {
         CaptureMode::Mic
     } else {
-        let mix_mode = if args.iter().any(|a| a == "--mix-mode=split") {
-            MixMode::Split
-        } else {
-            MixMode::Stereo
-        };
+        let mix_mode = args.iter().find_map(|a| match a.as_str() {
+            "--mix-mode=split" => Some(MixMode::Split),
+            "--mix-mode=stereo" => Some(MixMode::Stereo),
+            _ => None,
+        });
         CaptureMode::Both(mix_mode)
     };
+    if inputs.is_empty() {
+        inputs = default_inputs()
+            .into_iter()
+            .filter(|i| match mode {
+                CaptureMode::System => i.source == Source::System,
+                CaptureMode::Mic => i.source == Source::Mic,
+                CaptureMode::Both(_) => true,
+            })
+            .collect();
+    }
+
+    let system = SystemConfig {
+        include: args
+            .iter()
+            .find_map(|a| a.strip_prefix("--apps="))
+            .map(comma_list)
+            .unwrap_or_default(),
+        exclude: args
+            .iter()
+            .find_map(|a| a.strip_prefix("--exclude-apps="))
+            .map(comma_list)
+            .unwrap_or_default(),
+    };
+
+    let mic = MicConfig {
+        device: args
+            .iter()
+            .find_map(|a| a.strip_prefix("--device="))
+            .map(|s| s.to_string()),
+        sample_rate: args
+            .iter()
+            .find_map(|a| a.strip_prefix("--mic-rate="))
+            .and_then(|v| v.parse().ok()),
+        channels: args
+            .iter()
+            .find_map(|a| a.strip_prefix("--mic-channels="))
+            .and_then(|v| v.parse().ok()),
+    };
 
     let chunk_duration = args
         .iter()
@@ -65,16 +196,7 @@ fn parse_config() -> Config {
         .and_then(|v| v.parse().ok())
         .unwrap_or(0);
 
-    let default_output_dir = std::env::temp_dir()
-        .join("scribe")
-        .to_string_lossy()
-        .to_string();
-
-    let output_dir = args
-        .iter()
-        .find_map(|a| a.strip_prefix("--output-dir="))
-        .unwrap_or(&default_output_dir)
-        .to_string();
+    let output_dir = output_dir(&args);
 
     // Positional arg (first non-flag) or --output= sets transcript path
     let output = args
@@ -90,6 +212,39 @@ fn parse_config() -> Config {
 
     let no_transcribe = args.iter().any(|a| a == "--no-transcribe");
     let save_audio = args.iter().any(|a| a == "--save-audio");
+    let audio_format = match args.iter().find_map(|a| a.strip_prefix("--audio-format=")) {
+        Some(name) => AudioFormat::parse(name)?,
+        None => AudioFormat::Wav,
+    };
+
+    // Streaming needs a format that can be appended to: WAV or FLAC
+    let archive = args.iter().find_map(|a| match a.as_str() {
+        "--archive" | "--archive=wav" => Some(AudioFormat::Wav),
+        "--archive=flac" => Some(AudioFormat::Flac),
+        _ => None,
+    });
+    let tui = args.iter().any(|a| a == "--tui");
+    let summarize = args.iter().any(|a| a == "--summarize");
+
+    let rolling_summary = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--rolling-summary="))
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+
+    // Bare flag uses the default threshold
+    let low_confidence = args.iter().find_map(|a| match a.as_str() {
+        "--mark-low-confidence" => Some(0.5),
+        _ => a.strip_prefix("--mark-low-confidence=").and_then(|v| v.parse().ok()),
+    });
+
+    let templates = match args.iter().find_map(|a| a.strip_prefix("--templates=")) {
+        Some(dir) => template::Templates::load(Path::new(dir)).unwrap_or_else(|e| {
+            eprintln!("Ignoring --templates: {e}");
+            template::Templates::default()
+        }),
+        None => template::Templates::default(),
+    };
 
     let concurrency = args
         .iter()
@@ -97,6 +252,24 @@ fn parse_config() -> Config {
         .and_then(|v| v.parse().ok())
         .unwrap_or(2);
 
+    let max_pending = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--max-pending="))
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(20usize)
+        .max(1);
+
+    let max_disk_bytes = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--max-disk-mb="))
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|mb| mb * 1024 * 1024);
+
+    let backpressure = match args.iter().find_map(|a| a.strip_prefix("--backpressure=")) {
+        Some(name) => Backpressure::parse(name)?,
+        None => Backpressure::CaptureOnly,
+    };
+
     let use_local = args.iter().any(|a| a == "--local");
 
     let local_port = args
@@ -104,7 +277,30 @@ fn parse_config() -> Config {
         .find_map(|a| a.strip_prefix("--local-port="))
         .and_then(|v| v.parse().ok());
 
-    Config { mode, chunk_duration, overlap, output_dir, output, no_transcribe, save_audio, concurrency, use_local, local_port }
+    let title = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--title="))
+        .map(|s| s.to_string());
+
+    let tags = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--tags="))
+        .map(comma_list)
+        .unwrap_or_default();
+
+    Ok(Config { mode, inputs, system, mic, chunk_duration, overlap, output_dir, output, no_transcribe, save_audio, audio_format, archive, concurrency, max_pending, max_disk_bytes, backpressure, use_local, local_port, title, tags, tui, summarize, rolling_summary, low_confidence, templates })
+}
+
+fn output_dir(args: &[String]) -> String {
+    let default_output_dir = std::env::temp_dir()
+        .join("scribe")
+        .to_string_lossy()
+        .to_string();
+
+    args.iter()
+        .find_map(|a| a.strip_prefix("--output-dir="))
+        .unwrap_or(&default_output_dir)
+        .to_string()
 }
 
 fn main() {
@@ -119,26 +315,103 @@ fn print_help() {
 
 USAGE:
     scribe [FILE] [OPTIONS]
+    scribe list [--output-dir=PATH]
+    scribe devices
+    scribe apps
+    scribe status|pause|resume|stop|tail [N] [--output-dir=PATH]
+    scribe mark [NOTE] [--output-dir=PATH]
+    scribe summarize SESSION [OPTIONS]
+    scribe render SESSION [OPTIONS]
+    scribe transcribe FILE [OPTIONS]
+    scribe transcribe-dir PATH... [OPTIONS]
 
 By default, captures both channels and transcribes via OpenAI Whisper
 API (requires OPENAI_API_KEY). Use --local for local whisper-cpp server.
-Writes transcript to ./transcript-{{date}}.md
+Each run is a session with its own directory under OUTPUT_DIR/sessions/.
+Pause/resume a running session with `kill -USR1`/`kill -USR2` on its PID.
+A running session serves JSON-RPC on OUTPUT_DIR/scribe.sock.
+Writes transcript to ./transcript-{{session id}}.md
+
+COMMANDS:
+    list                   List recorded sessions
+    devices                List audio input devices and their supported formats
+    apps                   List running apps, for --apps/--exclude-apps
+    status                 Show the running session's state
+    pause, resume          Pause/resume the running session
+    stop                   Stop the running session
+    tail [N]               Print the last N transcript lines (default: 20)
+    mark [NOTE]            Bookmark the current moment, with an optional note
+    summarize SESSION      (Re)write summary.md/summary.json for a session
+                           (ID, ID prefix or directory)
+    render SESSION         Rewrite a session's markdown transcript from
+                           session.jsonl; --output=PATH (- for stdout),
+                           --no-timestamps, --merge-turns, --no-separators
+    transcribe FILE        Transcribe a recording (wav, flac, mp3, m4a, ogg) into
+                           a new session, in --chunk-duration pieces cut at
+                           pauses; --speaker=NAME names who's talking
+    transcribe-dir PATH... Transcribe saved HH-MM-SS_system/_mic chunk pairs in
+                           the given files or directories into one session per
+                           day, skipping pairs that already have chunk JSON
 
 OPTIONS:
     FILE                   Transcript output path (positional arg)
-    --output=PATH          Same as above, as a flag (default: transcript-{{date}}.md)
+    --output=PATH          Same as above, as a flag (default: transcript-{{session id}}.md)
     --output-dir=PATH      Intermediate files directory (default: /tmp/scribe)
+    --title=TEXT           Session title (used as the transcript heading)
+    --tags=A,B             Comma-separated session tags
+    --tui                  Live terminal UI: level meters, queue, transcript
+    --summarize            Summarize the session with an LLM when it ends
+    --summary-url=URL      Chat completions endpoint (default: OpenAI)
+    --summary-model=NAME   Summary model (default: gpt-4o-mini)
+    --summary-timeout=SECS Time to wait for each summary request (default: 600)
+    --rolling-summary=N    Update rolling-summary.md every N chunks while recording
     --chunk-duration=N     Chunk length in seconds (default: 30)
     --overlap=N            Overlap between chunks in seconds (default: 0)
     --concurrency=N        Transcription worker threads (default: 2)
+    --max-pending=N        Chunks waiting for a worker before backpressure (default: 20)
+    --max-disk-mb=N        Untranscribed chunk audio on disk before backpressure
+    --backpressure=POLICY  When over a limit: capture-only (stop transcribing,
+                           keep recording), drop (skip the chunk) or block
+                           (wait; audio is lost if capture overruns)
+                           (default: capture-only)
     --local                Use local whisper-cpp server instead of OpenAI API
     --model=NAME           Local whisper model size (default: large-v3-turbo)
     --local-port=N         Local whisper server port (default: 8080)
-    --save-audio           Keep WAV files after transcription
+    --save-audio           Keep chunk audio after transcription
+    --audio-format=FMT     Kept chunk audio as wav, flac or opus (default: wav)
+    --archive[=FMT]        Also record the whole session to one file in the
+                           session dir, one channel per input: wav or flac
+    --upload-format=FMT    Encoding sent to the API: wav, flac or opus
+                           (default: flac for OpenAI, wav otherwise)
     --no-transcribe        Capture only, no transcription
-    --system               Capture system audio only
-    --mic                  Capture microphone only
+    --system               Capture and transcribe system audio only (speaker: Other)
+    --mic                  Capture and transcribe the microphone only (speaker: You)
+    --device=NAME          Microphone whose name contains NAME (case-insensitive;
+                           default: system input). Reconnects if it goes away
+    --mic-rate=HZ          Ask the mic for this sample rate if it supports it
+                           (16000 skips resampling; default: device default)
+    --mic-channels=N       Ask the mic for this many channels (e.g. 1)
+    --apps=A,B             System audio only from apps whose name or bundle ID
+                           contains A or B (case-insensitive; must be running)
+    --exclude-apps=A,B     System audio from every app except these
+    --input=LABEL=SOURCE   Record SOURCE (system, system:APPS, mic or mic:DEVICE)
+                           as its own track, transcribed as speaker LABEL. Repeat
+                           for each input; replaces the default system + mic pair
+    --mix-mode=MODE        Chunk files: stereo (one file, a channel per input,
+                           split again for transcription) or split (default:
+                           split when transcribing, stereo otherwise)
     --api-url=URL          Custom transcription API endpoint
+    --glossary=FILE        Names/jargon to bias recognition toward, one per line
+    --vocab=A,B            Extra glossary terms, comma-separated
+    --replacements=FILE    Fix-ups applied to the text, one `wrong -> right` per line
+    --language=xx          Spoken language (ISO-639-1), instead of auto-detecting
+    --translate            Translate speech to English instead of transcribing
+    --mark-low-confidence[=P]
+                           Italicize words below probability P in the transcript
+                           (default: 0.5; needs word probabilities, e.g. --local)
+    --templates=DIR        Markdown layout from DIR/header.md, chunk.md and turn.md
+                           (see README); also used by render and transcribe
+    --keep-hallucinations  Don't filter silence/loop segments (see chunk JSON `dropped`)
     --transcribe=FILE      Transcribe a single WAV file
     --transcribe-pair=S,M  Transcribe a system,mic WAV pair
     -h, --help             Show this help");
@@ -152,6 +425,21 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    match args.get(1).map(String::as_str) {
+        Some("list") => return run_list(&args),
+        Some("devices") => return run_devices(),
+        Some("apps") => return run_apps(),
+        Some(cmd @ ("status" | "pause" | "resume" | "stop" | "tail")) => {
+            return run_control(cmd, &args);
+        }
+        Some("mark") => return run_control("bookmark", &args),
+        Some("summarize") => return run_summarize(&args),
+        Some("render") => return run_render(&args),
+        Some("transcribe") => return run_transcribe_file(&args),
+        Some("transcribe-dir") => return run_transcribe_dir(&args),
+        _ => {}
+    }
+
     if let Some(pair) = args.iter().find_map(|a| a.strip_prefix("--transcribe-pair=")) {
         return run_transcribe_pair(pair, &args);
     }
@@ -160,7 +448,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         return run_transcribe(path, &args);
     }
 
-    let config = parse_config();
+    let config = parse_config()?;
 
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
@@ -170,24 +458,15 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
 
     // Resolve transcription backend: --local → --api-url → OpenAI API (default)
     let _local_server;
-    let live_transcribe_config;
+    let mut live_transcribe_config;
 
-    if config.no_transcribe || !matches!(&config.mode, CaptureMode::Both(_)) {
+    if config.no_transcribe {
         _local_server = None;
         live_transcribe_config = None;
     } else if config.use_local {
         // --local: start local whisper-cpp server
-        let model = args
-            .iter()
-            .find_map(|a| a.strip_prefix("--model="))
-            .unwrap_or("large-v3-turbo");
-        match local::LocalServer::start(model, config.local_port) {
-            Ok(server) => {
-                let tc = transcribe::TranscribeConfig {
-                    api_key: String::new(),
-                    api_url: server.api_url(),
-                    model: String::new(),
-                };
+        match local_backend(&args, config.local_port) {
+            Ok((server, tc)) => {
                 _local_server = Some(server);
                 live_transcribe_config = Some(tc);
             }
@@ -211,79 +490,160 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    if let Some(tc) = &mut live_transcribe_config {
+        transcribe_options(tc, &args)?;
+    }
+
     let start = Instant::now();
 
     if config.chunk_duration > 0 {
+        let (backend, model) = match &live_transcribe_config {
+            Some(tc) => (backend_name(tc, config.use_local), Some(tc.model.clone())),
+            None => ("none", None),
+        };
+        let mode = match &config.mode {
+            CaptureMode::System => "system",
+            CaptureMode::Mic => "mic",
+            CaptureMode::Both(_) if config.inputs == default_inputs() => "both",
+            CaptureMode::Both(_) => &config.inputs.iter().map(|i| i.label.as_str()).collect::<Vec<_>>().join("+"),
+        };
+        let mut session = Session::create(&config.output_dir, SessionMeta {
+            title: config.title.clone(),
+            tags: config.tags.clone(),
+            mode: mode.to_string(),
+            backend: backend.to_string(),
+            model,
+            source: None,
+        })?;
+        eprintln!("Session {} ({})", session.id, session.dir.display());
+
         let chunk_config = ChunkConfig {
             chunk_duration: config.chunk_duration,
             overlap: config.overlap,
-            output_dir: config.output_dir.clone(),
+            audio_dir: session.audio_dir(),
+            format: config.audio_format,
+            archive: config
+                .archive
+                .map(|f| session.dir.join(format!("session.{}", f.extension()))),
+            max_pending: config.max_pending,
+            max_disk_bytes: config.max_disk_bytes,
+            backpressure: config.backpressure,
         };
 
-        match config.mode {
-            CaptureMode::System => {
-                let cap = SystemCapture::new()?;
-                cap.start()?;
-                eprintln!("Capturing system audio ({}s chunks)... Ctrl+C to stop.", chunk_config.chunk_duration);
-                chunker::run_chunked_single(&cap, "system", &chunk_config, &running)?;
-                cap.stop()?;
-            }
-            CaptureMode::Mic => {
-                let cap = MicCapture::new()?;
-                cap.start()?;
-                eprintln!("Capturing microphone ({}s chunks)... Ctrl+C to stop.", chunk_config.chunk_duration);
-                chunker::run_chunked_single(&cap, "mic", &chunk_config, &running)?;
-                cap.stop()?;
+        let status = Arc::new(Status::new());
+        control::install_signals(Arc::clone(&status));
+        let _control = match control::ControlServer::start(
+            control::socket_path(&config.output_dir),
+            &session,
+            Arc::clone(&status),
+            Arc::clone(&running),
+        ) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                eprintln!("Control socket unavailable: {e}");
+                None
             }
-            CaptureMode::Both(ref mix_mode) => {
-                let system = SystemCapture::new()?;
-                let mic = MicCapture::new()?;
-                system.start()?;
-                mic.start()?;
-
-                if let Some(tc) = live_transcribe_config {
-                    let live_mode = &MixMode::Split;
-                    let (tx, rx) = std::sync::mpsc::channel();
-
-                    let (date, _) = chunker::local_timestamp();
-                    let transcript_path = match &config.output {
-                        Some(p) => PathBuf::from(p),
-                        None => PathBuf::from(format!("transcript-{date}.md")),
-                    };
+        };
+        let tui = if config.tui {
+            let header = match &session.meta.title {
+                Some(title) => format!("scribe — {title}"),
+                None => format!("scribe — {}", session.id),
+            };
+            let log_path = session.dir.join("scribe.log");
+            eprintln!("Log: {}", log_path.display());
+            Some(tui::Tui::start(Arc::clone(&status), Arc::clone(&running), header, &log_path)?)
+        } else {
+            None
+        };
 
-                    let pipeline_config = pipeline::PipelineConfig {
-                        transcribe: tc,
-                        output_dir: config.output_dir.clone(),
-                        transcript_path: transcript_path.clone(),
-                        concurrency: config.concurrency,
-                        save_audio: config.save_audio,
-                    };
-                    let handles = pipeline::run(rx, pipeline_config);
+        // A single input always gets its own `{time}_{label}` file
+        let mix_mode = match config.mode {
+            CaptureMode::Both(Some(mix_mode)) => mix_mode,
+            CaptureMode::Both(None) if live_transcribe_config.is_none() => MixMode::Stereo,
+            _ => MixMode::Split,
+        };
+        if mix_mode == MixMode::Stereo && config.inputs.len() > 2 && config.audio_format == AudioFormat::Opus {
+            return Err("Opus holds at most 2 channels; use --mix-mode=split or another --audio-format".into());
+        }
+        let inputs = open_inputs(&config)?;
+        for input in &inputs {
+            input.capture.start()?;
+        }
 
-                    eprintln!("Transcribing to: {}", transcript_path.display());
-                    eprintln!("Capturing ({}s chunks, {} workers)... Ctrl+C to stop.",
-                        chunk_config.chunk_duration, config.concurrency);
-                    chunker::run_chunked_both(&system, &mic, live_mode, &chunk_config, &running, Some(&tx))?;
+        if let Some(tc) = live_transcribe_config {
+            let (tx, rx) = std::sync::mpsc::sync_channel(config.max_pending);
+
+            let transcript_path = match &config.output {
+                Some(p) => PathBuf::from(p),
+                None => PathBuf::from(format!("transcript-{}.md", session.id)),
+            };
+            session.transcript_path = Some(transcript_path.clone());
+            session.save()?;
+
+            let (results_tx, rolling) = if config.rolling_summary > 0 {
+                let (tx, handle) = summary::spawn_rolling(
+                    session.clone(),
+                    summary_config(&args),
+                    config.rolling_summary,
+                );
+                (Some(tx), Some(handle))
+            } else {
+                (None, None)
+            };
+
+            let pipeline_config = pipeline::PipelineConfig {
+                transcribe: tc,
+                session: session.clone(),
+                transcript_path: transcript_path.clone(),
+                concurrency: config.concurrency,
+                save_audio: config.save_audio,
+                audio_format: config.audio_format,
+                status: Arc::clone(&status),
+                markdown: markdown_options(&config),
+                results_tx,
+            };
+            let handles = pipeline::run(rx, pipeline_config);
+
+            eprintln!("Transcribing to: {}", transcript_path.display());
+            eprintln!("Capturing ({}s chunks, {} workers)... Ctrl+C to stop.",
+                chunk_config.chunk_duration, config.concurrency);
+            chunker::run_chunked(&inputs, &mix_mode, &chunk_config, &running, &status, Some(&tx))?;
+
+            drop(tx);
+            eprintln!("Waiting for transcription workers to finish...");
+            pipeline::shutdown(handles);
+            if let Some(handle) = rolling {
+                eprintln!("Finishing rolling summary...");
+                let _ = handle.join();
+            }
+            let leftover = status.take_markers().into_iter().map(|(_, m)| m).collect();
+            pipeline::write_markers(&session, &transcript_path, leftover, &markdown_options(&config))?;
 
-                    drop(tx);
-                    eprintln!("Waiting for transcription workers to finish...");
-                    pipeline::shutdown(handles);
+            if config.summarize
+                && let Err(e) = summary::summarize_session(&session, &summary_config(&args))
+            {
+                eprintln!("Summary failed: {e}");
+            }
+        } else {
+            let labels: Vec<&str> = inputs.iter().map(|i| i.label.as_str()).collect();
+            eprintln!("Capturing {} ({}s chunks)... Ctrl+C to stop.", labels.join(" + "), chunk_config.chunk_duration);
+            chunker::run_chunked(&inputs, &mix_mode, &chunk_config, &running, &status, None)?;
+        }
 
-                    eprintln!("Transcript: {}", transcript_path.display());
-                } else {
-                    eprintln!("Capturing system + mic ({}s chunks)... Ctrl+C to stop.", chunk_config.chunk_duration);
-                    chunker::run_chunked_both(&system, &mic, mix_mode, &chunk_config, &running, None)?;
-                }
+        for input in &inputs {
+            input.capture.stop()?;
+        }
 
-                system.stop()?;
-                mic.stop()?;
-            }
+        drop(tui);
+        if let Some(path) = &session.transcript_path {
+            eprintln!("Transcript: {}", path.display());
         }
+        session.finish()?;
     } else {
         match config.mode {
             CaptureMode::System => {
                 run_single(
-                    Box::new(SystemCapture::new()?),
+                    Box::new(SystemCapture::new(&config.system)?),
                     "system audio",
                     "output.wav",
                     &running,
@@ -291,14 +651,17 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             }
             CaptureMode::Mic => {
                 run_single(
-                    Box::new(MicCapture::new()?),
+                    Box::new(MicCapture::new(&config.mic)?),
                     "microphone",
                     "output_mic.wav",
                     &running,
                 )?;
             }
+            CaptureMode::Both(_) if config.inputs != default_inputs() => {
+                return Err("--input needs chunked capture (--chunk-duration > 0)".into());
+            }
             CaptureMode::Both(mix_mode) => {
-                run_both(mix_mode, &running)?;
+                run_both(mix_mode.unwrap_or(MixMode::Stereo), &config.system, &config.mic, &running)?;
             }
         }
     }
@@ -309,6 +672,39 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Open a capture for each input. Mic inputs without a device of their own
+/// use `--device`.
+fn open_inputs(config: &Config) -> Result<Vec<chunker::Input>, Box<dyn std::error::Error>> {
+    config
+        .inputs
+        .iter()
+        .map(|spec| {
+            let capture: Box<dyn Capture> = match spec.source {
+                Source::System => {
+                    let system = SystemConfig {
+                        include: if spec.apps.is_empty() { config.system.include.clone() } else { spec.apps.clone() },
+                        exclude: config.system.exclude.clone(),
+                    };
+                    Box::new(SystemCapture::new(&system)?)
+                }
+                Source::Mic => {
+                    let mic = MicConfig {
+                        device: spec.device.clone().or_else(|| config.mic.device.clone()),
+                        ..config.mic.clone()
+                    };
+                    Box::new(MicCapture::new(&mic)?)
+                }
+            };
+            Ok(chunker::Input {
+                label: spec.label.clone(),
+                speaker: spec.speaker.clone(),
+                source: spec.source,
+                capture,
+            })
+        })
+        .collect()
+}
+
 fn run_single(
     capture: Box<dyn Capture>,
     label: &str,
@@ -318,21 +714,17 @@ fn run_single(
     capture.start()?;
     eprintln!("Capturing {label}... Press Ctrl+C to stop.");
 
-    let rx = capture.rx();
+    let ring = capture.ring();
     let rate = capture.sample_rate();
     let channels = capture.channels();
 
     let mut samples: Vec<f32> = Vec::new();
     while running.load(Ordering::SeqCst) {
-        match rx.recv_timeout(std::time::Duration::from_millis(100)) {
-            Ok(chunk) => samples.extend(chunk),
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => continue,
-            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+        if ring.read_into(&mut samples) == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
         }
     }
-    while let Ok(chunk) = rx.try_recv() {
-        samples.extend(chunk);
-    }
+    ring.read_into(&mut samples);
 
     eprintln!("Stopping capture...");
     capture.stop()?;
@@ -347,10 +739,12 @@ fn run_single(
 
 fn run_both(
     mix_mode: MixMode,
+    system_config: &SystemConfig,
+    mic_config: &MicConfig,
     running: &AtomicBool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let system = SystemCapture::new()?;
-    let mic = MicCapture::new()?;
+    let system = SystemCapture::new(system_config)?;
+    let mic = MicCapture::new(mic_config)?;
 
     let sys_rate = system.sample_rate();
     let sys_ch = system.channels();
@@ -362,31 +756,19 @@ fn run_both(
     eprintln!("Capturing system audio + mic... Press Ctrl+C to stop.");
 
     // Inline dual capture loop
-    let sys_rx = system.rx();
-    let mic_rx = mic.rx();
+    let sys_ring = system.ring();
+    let mic_ring = mic.ring();
     let mut sys_samples: Vec<f32> = Vec::new();
     let mut mic_samples: Vec<f32> = Vec::new();
 
     while running.load(Ordering::SeqCst) {
-        let mut got_data = false;
-        while let Ok(chunk) = sys_rx.try_recv() {
-            sys_samples.extend(chunk);
-            got_data = true;
-        }
-        while let Ok(chunk) = mic_rx.try_recv() {
-            mic_samples.extend(chunk);
-            got_data = true;
-        }
-        if !got_data {
-            std::thread::sleep(std::time::Duration::from_millis(2));
+        let read = sys_ring.read_into(&mut sys_samples) + mic_ring.read_into(&mut mic_samples);
+        if read == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
         }
     }
-    while let Ok(chunk) = sys_rx.try_recv() {
-        sys_samples.extend(chunk);
-    }
-    while let Ok(chunk) = mic_rx.try_recv() {
-        mic_samples.extend(chunk);
-    }
+    sys_ring.read_into(&mut sys_samples);
+    mic_ring.read_into(&mut mic_samples);
 
     eprintln!("Stopping capture...");
     system.stop()?;
@@ -417,6 +799,228 @@ fn run_both(
     Ok(())
 }
 
+fn run_list(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let output_dir = output_dir(args);
+    let sessions = session::list(&output_dir);
+    if sessions.is_empty() {
+        eprintln!("No sessions in {output_dir}");
+        return Ok(());
+    }
+
+    for s in &sessions {
+        let end = match &s.ended_at {
+            Some(t) => t.split('T').nth(1).unwrap_or(t).to_string(),
+            None => "(interrupted)".to_string(),
+        };
+        let title = s.meta.title.as_deref().unwrap_or("-");
+        let model = s.meta.model.as_deref().unwrap_or("-");
+        let tags = if s.meta.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", s.meta.tags.join(", "))
+        };
+        println!(
+            "{}  {} → {}  {}  {}/{}  {title}{tags}",
+            s.id, s.started_at, end, s.meta.mode, s.meta.backend, model
+        );
+    }
+    Ok(())
+}
+
+fn run_devices() -> Result<(), Box<dyn std::error::Error>> {
+    for device in capture::input_devices()? {
+        let default = if device.is_default { " (default)" } else { "" };
+        println!("{}{default}", device.name);
+        for config in &device.configs {
+            println!("    {config}");
+        }
+    }
+    Ok(())
+}
+
+fn run_apps() -> Result<(), Box<dyn std::error::Error>> {
+    for app in capture::running_apps()? {
+        println!("{:<32} {}", app.name, app.bundle_id);
+    }
+    Ok(())
+}
+
+fn run_control(method: &str, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = control::socket_path(&output_dir(args));
+    let params = match method {
+        "tail" => {
+            let lines = args.get(2).and_then(|v| v.parse::<u64>().ok()).unwrap_or(20);
+            serde_json::json!({ "lines": lines })
+        }
+        "bookmark" => {
+            let note: Vec<&str> = args
+                .iter()
+                .skip(2)
+                .filter(|a| !a.starts_with("--"))
+                .map(String::as_str)
+                .collect();
+            if note.is_empty() {
+                serde_json::json!({})
+            } else {
+                serde_json::json!({ "note": note.join(" ") })
+            }
+        }
+        _ => serde_json::json!({}),
+    };
+    let result = control::call(&path, method, params)?;
+
+    match method {
+        "status" => print_status(&result),
+        "tail" => {
+            for line in result["lines"].as_array().into_iter().flatten() {
+                println!("{}", line.as_str().unwrap_or_default());
+            }
+        }
+        "pause" => eprintln!("Paused"),
+        "resume" => eprintln!("Resumed"),
+        "stop" => eprintln!("Stopping — pending chunks will finish transcribing"),
+        "bookmark" => eprintln!("Bookmarked {}", result["time"].as_str().unwrap_or_default()),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn print_status(s: &serde_json::Value) {
+    let title = s["title"].as_str().map(|t| format!(" \"{t}\"")).unwrap_or_default();
+    println!("Session {}{title} (pid {})", s["session_id"].as_str().unwrap_or("?"), s["pid"]);
+
+    let state = if !s["running"].as_bool().unwrap_or(false) {
+        "stopping"
+    } else if s["paused"].as_bool().unwrap_or(false) {
+        "paused"
+    } else {
+        "recording"
+    };
+    let elapsed = s["elapsed_seconds"].as_f64().unwrap_or(0.0) as u64;
+    println!("  {state}, {}:{:02}:{:02} elapsed", elapsed / 3600, (elapsed / 60) % 60, elapsed % 60);
+    println!(
+        "  chunks {} (current {:.1}s), queued {}, transcribing {}, done {}, failed {}",
+        s["chunks_flushed"],
+        s["current_chunk_seconds"].as_f64().unwrap_or(0.0),
+        s["queued"], s["in_flight"], s["done"], s["failed"]
+    );
+    let capture_only = if s["capture_only"].as_bool().unwrap_or(false) { ", capture only" } else { "" };
+    println!(
+        "  pending {:.1} MB, dropped {}, overruns {}{capture_only}",
+        s["pending_bytes"].as_f64().unwrap_or(0.0) / 1_048_576.0,
+        s["dropped"], s["overruns"]
+    );
+    println!("  bookmarks {}", s["bookmarks"]);
+    for m in s["meters"].as_array().into_iter().flatten() {
+        let db = |v: &serde_json::Value| 20.0 * v.as_f64().unwrap_or(0.0).max(1e-6).log10();
+        println!(
+            "  {:<7} {:>4.0} dB (peak {:.0} dB)",
+            m["label"].as_str().unwrap_or("?"),
+            db(&m["rms"]),
+            db(&m["peak"])
+        );
+    }
+}
+
+fn summary_config(args: &[String]) -> summary::SummaryConfig {
+    let defaults = summary::SummaryConfig::default();
+
+    // Key is optional so local OpenAI-compatible servers work without one
+    let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_default();
+
+    let api_url = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--summary-url="))
+        .map(|s| s.to_string())
+        .unwrap_or(defaults.api_url);
+
+    let model = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--summary-model="))
+        .map(|s| s.to_string())
+        .unwrap_or(defaults.model);
+
+    let timeout = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--summary-timeout="))
+        .and_then(|v| v.parse().ok())
+        .map_or(defaults.timeout, Duration::from_secs);
+
+    summary::SummaryConfig { api_key, api_url, model, timeout }
+}
+
+fn run_summarize(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let query = args
+        .iter()
+        .skip(2)
+        .find(|a| !a.starts_with("--"))
+        .ok_or("usage: scribe summarize SESSION")?;
+    let session = session::find(&output_dir(args), query)?;
+    summary::summarize_session(&session, &summary_config(args))
+}
+
+fn run_render(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let query = args
+        .iter()
+        .skip(2)
+        .find(|a| !a.starts_with("--"))
+        .ok_or("usage: scribe render SESSION")?;
+    let session = session::find(&output_dir(args), query)?;
+    let options = pipeline::RenderOptions {
+        timestamps: !args.iter().any(|a| a == "--no-timestamps"),
+        merge_turns: args.iter().any(|a| a == "--merge-turns"),
+        separators: !args.iter().any(|a| a == "--no-separators"),
+        ..markdown_options(&parse_config()?)
+    };
+    let markdown = pipeline::render(&session, &session.chunks()?, &options);
+
+    let path = match args.iter().find_map(|a| a.strip_prefix("--output=")) {
+        Some("-") => {
+            print!("{markdown}");
+            return Ok(());
+        }
+        Some(path) => PathBuf::from(path),
+        None => session
+            .transcript_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("transcript-{}.md", session.id))),
+    };
+    std::fs::write(&path, markdown)?;
+    eprintln!("Transcript: {}", path.display());
+    Ok(())
+}
+
+/// Start the whisper-cpp server for `--local`. Transcription only works
+/// while the returned server is alive.
+fn local_backend(
+    args: &[String],
+    port: Option<u16>,
+) -> Result<(local::LocalServer, transcribe::TranscribeConfig), Box<dyn std::error::Error>> {
+    let model = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--model="))
+        .unwrap_or("large-v3-turbo");
+    let server = local::LocalServer::start(model, port)?;
+    let tc = transcribe::TranscribeConfig {
+        api_key: String::new(),
+        api_url: server.api_url(),
+        model: model.to_string(),
+        ..Default::default()
+    };
+    Ok((server, tc))
+}
+
+/// Backend recorded in `session.json`.
+fn backend_name(tc: &transcribe::TranscribeConfig, use_local: bool) -> &'static str {
+    if use_local {
+        "local"
+    } else if tc.api_url == transcribe::TranscribeConfig::default().api_url {
+        "openai"
+    } else {
+        "api"
+    }
+}
+
 fn transcribe_config(args: &[String]) -> Result<transcribe::TranscribeConfig, Box<dyn std::error::Error>> {
     let api_key = std::env::var("OPENAI_API_KEY")
         .map_err(|_| "OPENAI_API_KEY not set")?;
@@ -433,12 +1037,133 @@ fn transcribe_config(args: &[String]) -> Result<transcribe::TranscribeConfig, Bo
         .unwrap_or("whisper-1")
         .to_string();
 
-    Ok(transcribe::TranscribeConfig { api_key, api_url, model })
+    Ok(transcribe::TranscribeConfig { api_key, api_url, model, ..Default::default() })
+}
+
+/// Backend-independent options: vocabulary, language, translation, filtering, upload format.
+fn transcribe_options(
+    config: &mut transcribe::TranscribeConfig,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    (config.glossary, config.replacements) = vocab::from_args(args)?;
+    config.language = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--language="))
+        .map(str::to_string);
+    config.translate = args.iter().any(|a| a == "--translate");
+    config.filter_hallucinations = !args.iter().any(|a| a == "--keep-hallucinations");
+    // whisper.cpp only decodes WAV; OpenAI takes FLAC at a fraction of the size
+    config.upload_format = match args.iter().find_map(|a| a.strip_prefix("--upload-format=")) {
+        Some(name) => AudioFormat::parse(name)?,
+        None if config.api_url == transcribe::TranscribeConfig::default().api_url => AudioFormat::Flac,
+        None => AudioFormat::Wav,
+    };
+    Ok(())
+}
+
+/// A backend for transcribing existing recordings: as for live capture,
+/// but without one there's nothing to do.
+fn batch_backend(
+    args: &[String],
+    config: &Config,
+) -> Result<(Option<local::LocalServer>, transcribe::TranscribeConfig), Box<dyn std::error::Error>> {
+    let (server, mut tc) = if config.use_local {
+        let (server, tc) = local_backend(args, config.local_port)?;
+        (Some(server), tc)
+    } else {
+        (None, transcribe_config(args)?)
+    };
+    transcribe_options(&mut tc, args)?;
+    Ok((server, tc))
+}
+
+fn batch_config(args: &[String], config: &Config, tc: &transcribe::TranscribeConfig) -> batch::BatchConfig {
+    batch::BatchConfig {
+        output_dir: config.output_dir.clone(),
+        chunk_duration: config.chunk_duration.max(1),
+        concurrency: config.concurrency.max(1),
+        speaker: args
+            .iter()
+            .find_map(|a| a.strip_prefix("--speaker="))
+            .unwrap_or("other")
+            .to_string(),
+        title: config.title.clone(),
+        tags: config.tags.clone(),
+        backend: backend_name(tc, config.use_local).to_string(),
+        transcript_path: args
+            .iter()
+            .find_map(|a| a.strip_prefix("--output="))
+            .map(PathBuf::from),
+        markdown: markdown_options(config),
+    }
+}
+
+/// Markdown layout from `--mark-low-confidence` and `--templates`.
+fn markdown_options(config: &Config) -> pipeline::RenderOptions {
+    pipeline::RenderOptions {
+        low_confidence: config.low_confidence,
+        templates: config.templates.clone(),
+        ..Default::default()
+    }
+}
+
+fn run_transcribe_file(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = args
+        .iter()
+        .skip(2)
+        .find(|a| !a.starts_with("--"))
+        .ok_or("usage: scribe transcribe FILE")?;
+    let config = parse_config()?;
+    let (_server, tc) = batch_backend(args, &config)?;
+    let batch = batch_config(args, &config, &tc);
+
+    let session = batch::transcribe_file(Path::new(path), tc, &batch)?;
+    if let Some(path) = &session.transcript_path {
+        eprintln!("Transcript: {}", path.display());
+    }
+    if config.summarize
+        && let Err(e) = summary::summarize_session(&session, &summary_config(args))
+    {
+        eprintln!("Summary failed: {e}");
+    }
+    Ok(())
+}
+
+fn run_transcribe_dir(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let paths: Vec<PathBuf> = args.iter().skip(2).filter(|a| !a.starts_with("--")).map(PathBuf::from).collect();
+    if paths.is_empty() {
+        return Err("usage: scribe transcribe-dir PATH...".into());
+    }
+    let (pairs, unpaired) = batch::find_pairs(&paths);
+    if unpaired > 0 {
+        eprintln!("Ignoring {unpaired} chunk files without a system/mic partner");
+    }
+    if pairs.is_empty() {
+        return Err("No *_system/*_mic pairs found".into());
+    }
+
+    let config = parse_config()?;
+    let (_server, tc) = batch_backend(args, &config)?;
+    let batch = batch_config(args, &config, &tc);
+
+    for session in batch::transcribe_pairs(pairs, tc, &batch)? {
+        if let Some(path) = &session.transcript_path {
+            eprintln!("Transcript: {}", path.display());
+        }
+        if config.summarize
+            && session.jsonl_path().exists()
+            && let Err(e) = summary::summarize_session(&session, &summary_config(args))
+        {
+            eprintln!("Summary failed for {}: {e}", session.id);
+        }
+    }
+    Ok(())
 }
 
 fn run_transcribe(path: &str, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
-    let config = transcribe_config(args)?;
-    let result = transcribe::transcribe(path, &config)?;
+    let mut config = transcribe_config(args)?;
+    transcribe_options(&mut config, args)?;
+    let result = transcribe::transcribe(path, &config, None)?;
     println!("{}", serde_json::to_string_pretty(&result)?);
     Ok(())
 }
@@ -448,15 +1173,19 @@ fn run_transcribe_pair(pair: &str, args: &[String]) -> Result<(), Box<dyn std::e
         .split_once(',')
         .ok_or("--transcribe-pair expects SYSTEM.wav,MIC.wav")?;
 
-    let config = transcribe_config(args)?;
+    let mut config = transcribe_config(args)?;
+    transcribe_options(&mut config, args)?;
 
     eprintln!("Transcribing system audio: {system_path}");
-    let system = transcribe::transcribe(system_path, &config)?;
+    let system = transcribe::transcribe(system_path, &config, None)?;
 
     eprintln!("Transcribing mic audio: {mic_path}");
-    let mic = transcribe::transcribe(mic_path, &config)?;
+    let mic = transcribe::transcribe(mic_path, &config, None)?;
 
-    let merged = transcribe::merge_transcripts(Some(system), Some(mic));
+    let merged = transcribe::merge_transcripts(vec![
+        transcribe::TrackTranscript { speaker: "other".into(), mic: false, transcript: Some(system) },
+        transcribe::TrackTranscript { speaker: "you".into(), mic: true, transcript: Some(mic) },
+    ]);
     println!("{}", serde_json::to_string_pretty(&merged)?);
     Ok(())
 }
@@ -1,5 +1,6 @@
 use rubato::{FftFixedIn, Resampler};
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum MixMode {
     Stereo,
     Split,
@@ -20,45 +21,71 @@ pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
     if from_rate == to_rate || samples.is_empty() {
         return samples.to_vec();
     }
+    let mut resampler = StreamResampler::new(from_rate, to_rate);
+    let mut output = resampler.process(samples);
+    output.extend(resampler.finish());
+    output
+}
+
+/// Resampling for mono audio that arrives a little at a time; input short
+/// of a full resampler chunk is held until the next call.
+pub struct StreamResampler {
+    /// None when the rates match.
+    resampler: Option<FftFixedIn<f32>>,
+    pending: Vec<f32>,
+    from_rate: u32,
+    to_rate: u32,
+}
 
-    let mut resampler = FftFixedIn::<f32>::new(
-        from_rate as usize,
-        to_rate as usize,
-        1024,
-        2,
-        1,
-    )
-    .expect("failed to create resampler");
+impl StreamResampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let resampler = (from_rate != to_rate).then(|| {
+            FftFixedIn::<f32>::new(from_rate as usize, to_rate as usize, 1024, 2, 1)
+                .expect("failed to create resampler")
+        });
+        Self { resampler, pending: Vec::new(), from_rate, to_rate }
+    }
 
-    let chunk_size = resampler.input_frames_next();
-    let mut output = Vec::new();
-    let mut pos = 0;
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        let Some(resampler) = self.resampler.as_mut() else {
+            return samples.to_vec();
+        };
+        self.pending.extend_from_slice(samples);
 
-    while pos + chunk_size <= samples.len() {
-        let chunk = &samples[pos..pos + chunk_size];
-        let result = resampler.process(&[chunk], None).expect("resample failed");
-        output.extend_from_slice(&result[0]);
-        pos += chunk_size;
+        let chunk_size = resampler.input_frames_next();
+        let mut output = Vec::new();
+        let mut pos = 0;
+        while pos + chunk_size <= self.pending.len() {
+            let chunk = &self.pending[pos..pos + chunk_size];
+            let result = resampler.process(&[chunk], None).expect("resample failed");
+            output.extend_from_slice(&result[0]);
+            pos += chunk_size;
+        }
+        self.pending.drain(..pos);
+        output
     }
 
-    // Handle remainder — zero-pad to chunk_size, trim output proportionally
-    if pos < samples.len() {
-        let remaining = samples.len() - pos;
-        let mut last_chunk = vec![0.0f32; chunk_size];
-        last_chunk[..remaining].copy_from_slice(&samples[pos..]);
+    /// Whatever is still held, zero-padded to a full chunk with the output
+    /// trimmed proportionally.
+    pub fn finish(mut self) -> Vec<f32> {
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Vec::new();
+        };
+        if self.pending.is_empty() {
+            return Vec::new();
+        }
+        let remaining = self.pending.len();
+        let mut last_chunk = vec![0.0f32; resampler.input_frames_next()];
+        last_chunk[..remaining].copy_from_slice(&self.pending);
         let result = resampler
             .process(&[&last_chunk], None)
             .expect("resample failed");
-        let expected = (remaining as f64 * to_rate as f64 / from_rate as f64).ceil() as usize;
+        let expected = (remaining as f64 * self.to_rate as f64 / self.from_rate as f64).ceil() as usize;
         let take = expected.min(result[0].len());
-        output.extend_from_slice(&result[0][..take]);
+        result[0][..take].to_vec()
     }
-
-    output
 }
 
-/// Scale samples so peak amplitude reaches `target` (0.0–1.0).
-/// Returns unchanged if silent.
 pub fn peak_normalize(samples: &mut [f32], target: f32) {
     let peak = samples.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
     if peak > 0.0 {
@@ -70,11 +97,15 @@ pub fn peak_normalize(samples: &mut [f32], target: f32) {
 }
 
 pub fn interleave_stereo(system: &[f32], mic: &[f32]) -> Vec<f32> {
-    let len = system.len().max(mic.len());
-    let mut out = Vec::with_capacity(len * 2);
+    interleave(&[system, mic])
+}
+
+/// One channel per track, padding the shorter tracks with silence.
+pub fn interleave(tracks: &[&[f32]]) -> Vec<f32> {
+    let len = tracks.iter().map(|t| t.len()).max().unwrap_or(0);
+    let mut out = Vec::with_capacity(len * tracks.len());
     for i in 0..len {
-        out.push(system.get(i).copied().unwrap_or(0.0));
-        out.push(mic.get(i).copied().unwrap_or(0.0));
+        out.extend(tracks.iter().map(|t| t.get(i).copied().unwrap_or(0.0)));
     }
     out
 }
@@ -1,79 +1,203 @@
-use crate::transcribe::{self, SpeakerSegment, TranscribeConfig};
-use serde::Serialize;
+use crate::audio::{self, AudioFormat};
+use crate::capture::Source;
+use crate::session::Session;
+use crate::status::Status;
+use crate::template::{self, Templates};
+use crate::transcribe::{self, DroppedSegment, SpeakerSegment, TrackTranscript, TranscribeConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+/// One input's audio for a chunk.
+pub struct Track {
+    /// Input name, used in file names and as the key in `audio_files`.
+    pub label: String,
+    /// Who the track's segments are attributed to.
+    pub speaker: String,
+    pub source: Source,
+    pub path: PathBuf,
+    /// The track's channel of `path`, when the chunk was written mixed
+    /// into one file with a channel per input.
+    pub channel: Option<u16>,
+}
 
-pub struct ChunkPair {
+/// The audio of every input over the same stretch of time.
+pub struct ChunkSet {
     pub timestamp: String,
-    pub date: String,
-    pub system_path: PathBuf,
-    pub mic_path: PathBuf,
+    /// When the chunk ends, if known up front (transcribing a file). Live
+    /// chunks are stamped when they're transcribed.
+    pub timestamp_end: Option<String>,
+    /// Stem of the chunk's file names when `timestamp` alone could clash,
+    /// as with backfilled chunks from several sessions.
+    pub name: Option<String>,
+    pub tracks: Vec<Track>,
+    pub markers: Vec<Marker>,
+    /// Size of the chunk's audio files, counted against the disk limit.
+    pub bytes: u64,
+}
+
+impl ChunkSet {
+    /// Stem of the chunk JSON and extracted WAV names.
+    pub fn stem(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.timestamp)
+    }
+
+    /// The chunk's audio files; mixed tracks share one.
+    pub fn files(&self) -> Vec<&PathBuf> {
+        let mut files: Vec<&PathBuf> = self.tracks.iter().map(|t| &t.path).collect();
+        files.dedup();
+        files
+    }
+}
+
+/// Non-speech events recorded in the transcript alongside a chunk.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Marker {
+    /// Capture was paused between these wall-clock times ("14-32-05").
+    /// Always sits at the start of the chunk that follows the pause.
+    Paused { from: String, to: String },
+    /// Chunks from `from` weren't transcribed because the pipeline was
+    /// backlogged. `to` is where transcription picked up again; `None`
+    /// if the session went capture-only.
+    Untranscribed { from: String, to: Option<String> },
+    /// An input's microphone was lost or came back, `offset` seconds into
+    /// the chunk.
+    Device { time: String, offset: f64, input: String, message: String },
+    /// User bookmark at `offset` seconds into the chunk.
+    Bookmark {
+        time: String,
+        offset: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+}
+
+impl Marker {
+    /// Position in seconds from the start of the chunk.
+    pub fn offset(&self) -> f64 {
+        match self {
+            Marker::Paused { .. } | Marker::Untranscribed { .. } => 0.0,
+            Marker::Bookmark { offset, .. } | Marker::Device { offset, .. } => *offset,
+        }
+    }
+
+    /// Anchor the marker in a chunk starting at `chunk_start` and lasting `duration` seconds.
+    pub fn place(mut self, at: Instant, chunk_start: Instant, duration: f64) -> Self {
+        if let Marker::Bookmark { offset, .. } | Marker::Device { offset, .. } = &mut self {
+            *offset = at.saturating_duration_since(chunk_start).as_secs_f64().min(duration);
+        }
+        self
+    }
 }
 
 pub struct PipelineConfig {
     pub transcribe: TranscribeConfig,
-    pub output_dir: String,
+    pub session: Session,
     pub transcript_path: PathBuf,
     pub concurrency: usize,
     pub save_audio: bool,
+    /// Format kept chunk audio is re-encoded to with `save_audio`.
+    pub audio_format: AudioFormat,
+    pub status: Arc<Status>,
+    /// Layout of the markdown transcript.
+    pub markdown: RenderOptions,
+    /// Downstream stage fed each written chunk (rolling summary).
+    pub results_tx: Option<Sender<ChunkResult>>,
 }
 
-#[derive(Serialize)]
-struct AudioFiles {
-    system: String,
-    mic: String,
+/// Audio file of each track of a chunk, by track label.
+pub type AudioFiles = BTreeMap<String, String>;
+
+/// Language reported for each track of a chunk, by track label.
+pub type Languages = BTreeMap<String, String>;
+
+/// One line of `session.jsonl`.
+#[derive(Deserialize, Serialize)]
+pub struct ChunkResult {
+    pub timestamp_start: String,
+    pub timestamp_end: String,
+    pub duration_seconds: f64,
+    pub segments: Vec<SpeakerSegment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_files: Option<AudioFiles>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub languages: Option<Languages>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub markers: Vec<Marker>,
+    /// Segments filtered out as likely hallucinations.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<DroppedSegment>,
 }
 
-#[derive(Serialize)]
-struct ChunkResult {
-    timestamp_start: String,
-    timestamp_end: String,
-    duration_seconds: f64,
-    segments: Vec<SpeakerSegment>,
-    audio_files: AudioFiles,
-}
+/// Latest transcribed text per track, with the timestamp of the chunk it
+/// came from so a worker finishing out of order doesn't roll it back.
+type Context = Mutex<HashMap<String, (String, String)>>;
 
-pub fn run(rx: Receiver<ChunkPair>, config: PipelineConfig) -> Vec<JoinHandle<()>> {
+pub fn run(rx: Receiver<ChunkSet>, config: PipelineConfig) -> Vec<JoinHandle<()>> {
     let rx = Arc::new(Mutex::new(rx));
     let config = Arc::new(config);
+    let context = Arc::new(Context::default());
     let mut handles = Vec::with_capacity(config.concurrency);
 
     for i in 0..config.concurrency {
         let rx = Arc::clone(&rx);
         let config = Arc::clone(&config);
-        handles.push(thread::spawn(move || worker(i, rx, config)));
+        let context = Arc::clone(&context);
+        handles.push(thread::spawn(move || worker(i, rx, config, context)));
     }
 
     handles
 }
 
-fn worker(id: usize, rx: Arc<Mutex<Receiver<ChunkPair>>>, config: Arc<PipelineConfig>) {
+fn worker(
+    id: usize,
+    rx: Arc<Mutex<Receiver<ChunkSet>>>,
+    config: Arc<PipelineConfig>,
+    context: Arc<Context>,
+) {
     loop {
-        let pair = {
+        let chunk = {
             let lock = rx.lock().unwrap();
             lock.recv()
         };
 
-        let pair = match pair {
+        let chunk = match chunk {
             Ok(p) => p,
             Err(_) => break, // channel closed
         };
 
-        eprintln!("[worker {id}] transcribing chunk {}", pair.timestamp);
+        eprintln!("[worker {id}] transcribing chunk {}", chunk.timestamp);
+        config.status.chunk_started();
+
+        let result = match process_chunk(&chunk, &config, &context) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("[worker {id}] error processing {}: {e}", chunk.timestamp);
+                if let Err(e) = write_chunk_markers(&chunk, &config) {
+                    eprintln!("[worker {id}] markers of {} lost: {e}", chunk.timestamp);
+                }
+                config.status.chunk_finished(false, chunk.bytes);
+                // keep WAVs for --transcribe-pair retry
+                continue;
+            }
+        };
+        config.status.chunk_finished(true, chunk.bytes);
 
-        if let Err(e) = process_chunk(&pair, &config) {
-            eprintln!("[worker {id}] error processing {}: {e}", pair.timestamp);
-            // keep WAVs for --transcribe-pair retry
-            continue;
+        if let (Some(result), Some(tx)) = (result, &config.results_tx) {
+            let _ = tx.send(result);
         }
 
         if !config.save_audio {
-            let _ = fs::remove_file(&pair.system_path);
-            let _ = fs::remove_file(&pair.mic_path);
+            for file in chunk.files() {
+                let _ = fs::remove_file(file);
+            }
         }
     }
 }
@@ -106,77 +230,282 @@ fn is_silent(path: &PathBuf) -> bool {
     rms < SILENCE_RMS_THRESHOLD
 }
 
-fn process_chunk(
-    pair: &ChunkPair,
-    config: &PipelineConfig,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let sys_path_str = pair.system_path.to_str().unwrap();
-    let mic_path_str = pair.mic_path.to_str().unwrap();
+/// With `save_audio`, re-encode the chunk WAVs in the archive format.
+/// Returns where each track's audio now lives.
+fn archive(chunk: &ChunkSet, config: &PipelineConfig) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut done: Vec<(&PathBuf, PathBuf)> = Vec::new();
+    for file in chunk.files() {
+        let kept = if config.save_audio {
+            audio::transcode(file, config.audio_format)?
+        } else {
+            file.clone()
+        };
+        done.push((file, kept));
+    }
+    Ok(chunk
+        .tracks
+        .iter()
+        .map(|track| done.iter().find(|(file, _)| *file == &track.path).unwrap().1.clone())
+        .collect())
+}
 
-    let sys_silent = is_silent(&pair.system_path);
-    let mic_silent = is_silent(&pair.mic_path);
+/// A mono WAV per track to transcribe. Mixed chunks are split into
+/// temporary per-channel files, returned second for cleanup.
+fn track_files(chunk: &ChunkSet) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Box<dyn std::error::Error>> {
+    let mut files = Vec::with_capacity(chunk.tracks.len());
+    let mut temporary = Vec::new();
+    for track in &chunk.tracks {
+        let Some(channel) = track.channel else {
+            files.push(track.path.clone());
+            continue;
+        };
+        let path = track.path.with_file_name(format!("{}_{}.wav", chunk.stem(), track.label));
+        audio::extract_channel(&track.path, channel, &path)?;
+        files.push(path.clone());
+        temporary.push(path);
+    }
+    Ok((files, temporary))
+}
 
-    if sys_silent && mic_silent {
-        eprintln!("  both channels silent, skipping");
-        return Ok(());
+/// Transcribe each track's mono `files`, skipping silent ones. `None` if
+/// every track was silent and there are no markers to write.
+fn transcribe_tracks(
+    chunk: &ChunkSet,
+    files: &[PathBuf],
+    config: &PipelineConfig,
+    context: &Context,
+) -> Result<Option<Vec<Option<transcribe::Transcript>>>, Box<dyn std::error::Error>> {
+    let silent: Vec<bool> = files.iter().map(is_silent).collect();
+    if silent.iter().all(|&s| s) && chunk.markers.is_empty() {
+        return Ok(None);
     }
 
-    let system = if sys_silent {
-        eprintln!("  system channel silent, skipping");
-        None
-    } else {
-        Some(transcribe::transcribe(sys_path_str, &config.transcribe)?)
-    };
+    let mut transcripts = Vec::with_capacity(files.len());
+    for ((track, path), silent) in chunk.tracks.iter().zip(files).zip(silent) {
+        if silent {
+            eprintln!("  {} channel silent, skipping", track.label);
+            transcripts.push(None);
+            continue;
+        }
+        let prev = context.lock().unwrap().get(&track.label).map(|(_, text)| text.clone());
+        let path = path.to_str().unwrap();
+        transcripts.push(Some(transcribe::transcribe(path, &config.transcribe, prev.as_deref())?));
+    }
+    Ok(Some(transcripts))
+}
 
-    let mic = if mic_silent {
-        eprintln!("  mic channel silent, skipping");
-        None
-    } else {
-        Some(transcribe::transcribe(mic_path_str, &config.transcribe)?)
+/// Transcribe and write one chunk. Returns the result, or `None` if the
+/// chunk was silent and nothing was written.
+fn process_chunk(
+    chunk: &ChunkSet,
+    config: &PipelineConfig,
+    context: &Context,
+) -> Result<Option<ChunkResult>, Box<dyn std::error::Error>> {
+    let (files, temporary) = track_files(chunk)?;
+    let transcripts = transcribe_tracks(chunk, &files, config, context);
+    for path in &temporary {
+        let _ = fs::remove_file(path);
+    }
+    let Some(transcripts) = transcripts? else {
+        eprintln!("  all channels silent, skipping");
+        archive(chunk, config)?;
+        return Ok(None);
     };
 
-    let merged = transcribe::merge_transcripts(system, mic);
+    {
+        let mut context = context.lock().unwrap();
+        for (track, t) in chunk.tracks.iter().zip(&transcripts) {
+            let Some(t) = t.as_ref().filter(|t| !t.text.trim().is_empty()) else { continue };
+            let newer = context.get(&track.label).is_none_or(|(ts, _)| *ts <= chunk.timestamp);
+            if newer {
+                context.insert(track.label.clone(), (chunk.timestamp.clone(), t.text.clone()));
+            }
+        }
+    }
 
-    let (_, end_time) = crate::chunker::local_timestamp();
+    let languages: Languages = chunk
+        .tracks
+        .iter()
+        .zip(&transcripts)
+        .filter_map(|(track, t)| Some((track.label.clone(), t.as_ref()?.language.clone()?)))
+        .collect();
+    let languages = (!languages.is_empty()).then_some(languages);
+
+    let merged = transcribe::merge_transcripts(
+        chunk
+            .tracks
+            .iter()
+            .zip(transcripts)
+            .map(|(track, transcript)| TrackTranscript {
+                speaker: track.speaker.clone(),
+                mic: track.source == Source::Mic,
+                transcript,
+            })
+            .collect(),
+    );
+    let audio_files: AudioFiles = chunk
+        .tracks
+        .iter()
+        .zip(archive(chunk, config)?)
+        .map(|(track, file)| (track.label.clone(), file.to_string_lossy().to_string()))
+        .collect();
+
+    let end_time = match &chunk.timestamp_end {
+        Some(end) => end.clone(),
+        None => crate::chunker::local_timestamp().1,
+    };
 
     let result = ChunkResult {
-        timestamp_start: pair.timestamp.clone(),
+        timestamp_start: chunk.timestamp.clone(),
         timestamp_end: end_time,
         duration_seconds: merged.duration,
         segments: merged.segments,
-        audio_files: AudioFiles {
-            system: pair.system_path.to_string_lossy().to_string(),
-            mic: pair.mic_path.to_string_lossy().to_string(),
-        },
+        audio_files: Some(audio_files),
+        languages,
+        markers: chunk.markers.clone(),
+        dropped: merged.dropped,
     };
 
     // Write individual chunk JSON
-    let transcript_dir = PathBuf::from(&config.output_dir)
-        .join("transcripts")
-        .join(&pair.date);
-    fs::create_dir_all(&transcript_dir)?;
+    let chunks_dir = config.session.chunks_dir();
+    fs::create_dir_all(&chunks_dir)?;
 
-    let json_path = transcript_dir.join(format!("{}.json", pair.timestamp));
+    let json_path = chunks_dir.join(format!("{}.json", chunk.stem()));
     let json = serde_json::to_string_pretty(&result)?;
     fs::write(&json_path, &json)?;
 
-    // Append to session.jsonl
-    let jsonl_path = transcript_dir.join("session.jsonl");
-    let line = serde_json::to_string(&result)?;
+    append_jsonl(&config.session, &result)?;
+
+    // Append to transcript markdown
+    append_markdown(&config.transcript_path, &config.session, &result, &config.markdown)?;
+
+    for marker in &result.markers {
+        config.status.push_transcript(format_marker(marker));
+    }
+    let chunk_start = format_timestamp(&result.timestamp_start);
+    for seg in &result.segments {
+        let line = format!(
+            "{chunk_start} +{} {}: {}",
+            format_time(seg.start),
+            speaker_label(&seg.speaker),
+            seg.text.trim()
+        );
+        config.status.push_transcript(line);
+    }
+
+    eprintln!("  wrote {}", json_path.display());
+    Ok(Some(result))
+}
+
+/// Record the markers of a chunk that couldn't be transcribed, so pauses,
+/// device changes and bookmarks taken during it aren't lost with it.
+fn write_chunk_markers(chunk: &ChunkSet, config: &PipelineConfig) -> Result<(), Box<dyn std::error::Error>> {
+    if chunk.markers.is_empty() {
+        return Ok(());
+    }
+    let result = ChunkResult {
+        timestamp_start: chunk.timestamp.clone(),
+        timestamp_end: chunk.timestamp_end.clone().unwrap_or_else(|| chunk.timestamp.clone()),
+        duration_seconds: 0.0,
+        segments: Vec::new(),
+        audio_files: None,
+        languages: None,
+        markers: chunk.markers.clone(),
+        dropped: Vec::new(),
+    };
+    append_jsonl(&config.session, &result)?;
+    append_markdown(&config.transcript_path, &config.session, &result, &config.markdown)?;
+    for marker in &result.markers {
+        config.status.push_transcript(format_marker(marker));
+    }
+    Ok(())
+}
+
+fn append_jsonl(session: &Session, result: &ChunkResult) -> Result<(), Box<dyn std::error::Error>> {
+    let line = serde_json::to_string(result)?;
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open(&jsonl_path)?;
+        .open(session.jsonl_path())?;
     writeln!(file, "{line}")?;
+    Ok(())
+}
 
-    // Append to transcript markdown
-    append_markdown(&config.transcript_path, &result)?;
+/// Write markers that never got a chunk to ride on (e.g. the session
+/// was stopped while paused). Call after `shutdown` so they land last.
+pub fn write_markers(
+    session: &Session,
+    transcript_path: &PathBuf,
+    markers: Vec<Marker>,
+    options: &RenderOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if markers.is_empty() {
+        return Ok(());
+    }
 
-    eprintln!("  wrote {}", json_path.display());
+    let (_, now) = crate::chunker::local_timestamp();
+    let result = ChunkResult {
+        timestamp_start: now.clone(),
+        timestamp_end: now,
+        duration_seconds: 0.0,
+        segments: Vec::new(),
+        audio_files: None,
+        languages: None,
+        markers,
+        dropped: Vec::new(),
+    };
+    append_jsonl(session, &result)?;
+    append_markdown(transcript_path, session, &result, options)
+}
+
+/// Rewrite the markdown transcript from `session.jsonl`, in chunk order.
+/// Workers append chunks as they finish, which can be out of order.
+pub fn rewrite_markdown(
+    session: &Session,
+    transcript_path: &PathBuf,
+    options: &RenderOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(transcript_path, render(session, &session.chunks()?, options))?;
     Ok(())
 }
 
-fn format_timestamp(ts: &str) -> String {
+/// How a transcript is laid out. The default is what's written live
+/// without `--templates`.
+#[derive(Clone)]
+pub struct RenderOptions {
+    /// Chunk headings and each turn's offset into its chunk.
+    pub timestamps: bool,
+    /// Join a speaker's turns across chunk boundaries; the chunks then
+    /// share one heading.
+    pub merge_turns: bool,
+    /// `---` after each chunk.
+    pub separators: bool,
+    /// Italicize words below this probability.
+    pub low_confidence: Option<f64>,
+    pub templates: Templates,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            timestamps: true,
+            merge_turns: false,
+            separators: true,
+            low_confidence: None,
+            templates: Templates::default(),
+        }
+    }
+}
+
+/// The whole markdown transcript for `chunks`, which should be in
+/// recording order.
+pub fn render(session: &Session, chunks: &[ChunkResult], options: &RenderOptions) -> String {
+    let mut out = header(session, options);
+    write_blocks(&mut out, session, &blocks(chunks, options), options);
+    out
+}
+
+pub(crate) fn format_timestamp(ts: &str) -> String {
     ts.replace('-', ":")
 }
 
@@ -190,46 +519,241 @@ fn format_time(seconds: f64) -> String {
     }
 }
 
-fn append_markdown(path: &PathBuf, result: &ChunkResult) -> Result<(), Box<dyn std::error::Error>> {
-    let is_new = !path.exists() || fs::metadata(path).map_or(true, |m| m.len() == 0);
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(path)?;
+/// "14-32-05" → "14:32"
+pub(crate) fn format_clock(ts: &str) -> String {
+    ts.split('-').take(2).collect::<Vec<_>>().join(":")
+}
 
-    if is_new {
-        let (date_str, _) = crate::chunker::local_timestamp();
-        writeln!(file, "# Transcript — {date_str}\n")?;
+pub(crate) fn format_marker(marker: &Marker) -> String {
+    match marker {
+        Marker::Paused { from, to } => {
+            format!("[paused {}–{}]", format_clock(from), format_clock(to))
+        }
+        Marker::Untranscribed { from, to: Some(to) } => {
+            format!("[not transcribed {}–{}: transcription backlog]", format_clock(from), format_clock(to))
+        }
+        Marker::Untranscribed { from, to: None } => {
+            format!("[not transcribed from {}: transcription backlog, audio kept]", format_clock(from))
+        }
+        Marker::Device { time, input, message, .. } => format!("[{input} {}] {message}", format_clock(time)),
+        Marker::Bookmark { time, note: Some(note), .. } => {
+            format!("[bookmark {}] {note}", format_clock(time))
+        }
+        Marker::Bookmark { time, note: None, .. } => {
+            format!("[bookmark {}]", format_clock(time))
+        }
     }
+}
 
-    let start = format_timestamp(&result.timestamp_start);
-    let end = format_timestamp(&result.timestamp_end);
-    let dur = format_time(result.duration_seconds);
-    writeln!(file, "## {start} — {end} ({dur})\n")?;
+pub(crate) fn speaker_label(speaker: &str) -> &str {
+    match speaker {
+        "you" => "You",
+        "other" => "Other",
+        s => s,
+    }
+}
 
-    // Merge consecutive same-speaker segments
-    let mut merged: Vec<(&str, f64, String)> = Vec::new();
-    for seg in &result.segments {
-        if let Some(last) = merged.last_mut() {
-            if last.0 == seg.speaker {
-                last.2.push_str(&seg.text);
+/// Segment text with words below `threshold` probability in italics.
+/// Words are located in order in the text so its spacing and punctuation
+/// survive; falls back to the plain text if they can't be lined up.
+fn marked_text(seg: &SpeakerSegment, threshold: Option<f64>) -> String {
+    let Some(threshold) = threshold else {
+        return seg.text.clone();
+    };
+    if !seg.words.iter().any(|w| w.probability.is_some_and(|p| p < threshold)) {
+        return seg.text.clone();
+    }
+
+    let mut out = String::with_capacity(seg.text.len() + 16);
+    let mut rest = seg.text.as_str();
+    for w in &seg.words {
+        let word = w.word.trim();
+        if word.is_empty() {
+            continue;
+        }
+        let Some(i) = rest.find(word) else {
+            return seg.text.clone();
+        };
+        out.push_str(&rest[..i]);
+        if w.probability.is_some_and(|p| p < threshold) {
+            out.push('*');
+            out.push_str(word);
+            out.push('*');
+        } else {
+            out.push_str(word);
+        }
+        rest = &rest[i + word.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+enum Entry<'a> {
+    Turn(&'a str, f64, String),
+    Mark(&'a Marker),
+}
+
+/// A piece of the transcript: a marker outside any chunk's segments, or
+/// the turns of one chunk (several, with `merge_turns`).
+enum Block<'a> {
+    Mark(&'a Marker),
+    Section { start: &'a str, end: &'a str, duration: f64, entries: Vec<Entry<'a>> },
+}
+
+fn blocks<'a>(chunks: &'a [ChunkResult], options: &RenderOptions) -> Vec<Block<'a>> {
+    let mut blocks = Vec::new();
+    for result in chunks {
+        // Gaps lead the chunk; bookmarks and device changes sit between the
+        // segments around them
+        let (leading, mut inline): (Vec<&Marker>, Vec<&Marker>) = result
+            .markers
+            .iter()
+            .partition(|m| !matches!(m, Marker::Bookmark { .. } | Marker::Device { .. }) || result.segments.is_empty());
+        inline.sort_by(|a, b| a.offset().partial_cmp(&b.offset()).unwrap());
+
+        let continues = leading.is_empty();
+        blocks.extend(leading.into_iter().map(Block::Mark));
+        if result.segments.is_empty() {
+            continue;
+        }
+
+        // Merge consecutive same-speaker segments, unless a marker falls between them
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut marks = inline.into_iter().peekable();
+        for seg in &result.segments {
+            while let Some(m) = marks.next_if(|m| m.offset() < seg.start) {
+                entries.push(Entry::Mark(m));
+            }
+            if let Some(Entry::Turn(speaker, _, text)) = entries.last_mut()
+                && *speaker == seg.speaker
+            {
+                text.push_str(&marked_text(seg, options.low_confidence));
                 continue;
             }
+            entries.push(Entry::Turn(&seg.speaker, seg.start, marked_text(seg, options.low_confidence)));
         }
-        merged.push((&seg.speaker, seg.start, seg.text.clone()));
+        entries.extend(marks.map(Entry::Mark));
+
+        if options.merge_turns
+            && continues
+            && let Some(Block::Section { start, end, duration, entries: previous }) = blocks.last_mut()
+            && let Some(Entry::Turn(speaker, _, text)) = previous.last_mut()
+            && let Some(Entry::Turn(next, _, _)) = entries.first()
+            && speaker == next
+        {
+            let Entry::Turn(_, _, next_text) = entries.remove(0) else { unreachable!() };
+            text.push(' ');
+            text.push_str(next_text.trim_start());
+            // Turn offsets count from the start of the section
+            let offset = (clock_secs(&result.timestamp_start) - clock_secs(start)).rem_euclid(86400.0);
+            for entry in &mut entries {
+                if let Entry::Turn(_, at, _) = entry {
+                    *at += offset;
+                }
+            }
+            previous.extend(entries);
+            *end = &result.timestamp_end;
+            *duration += result.duration_seconds;
+            continue;
+        }
+
+        blocks.push(Block::Section {
+            start: &result.timestamp_start,
+            end: &result.timestamp_end,
+            duration: result.duration_seconds,
+            entries,
+        });
     }
+    blocks
+}
+
+/// Template variables describing the session.
+fn session_vars(session: &Session) -> Vec<(&'static str, String)> {
+    vec![
+        ("heading", session.heading()),
+        ("title", session.meta.title.clone().unwrap_or_default()),
+        ("date", session.date().to_string()),
+        ("id", session.id.clone()),
+        ("started", session.started_at.clone()),
+        ("ended", session.ended_at.clone().unwrap_or_default()),
+        ("tags", session.meta.tags.join(", ")),
+        ("mode", session.meta.mode.clone()),
+        ("backend", session.meta.backend.clone()),
+        ("model", session.meta.model.clone().unwrap_or_default()),
+    ]
+}
+
+fn fill(template: &str, vars: &[(&str, String)]) -> String {
+    let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    template::fill(template, &vars)
+}
 
-    for (speaker, start, text) in &merged {
-        let label = match speaker.as_ref() {
-            "you" => "You",
-            "other" => "Other",
-            s => s,
+fn header(session: &Session, options: &RenderOptions) -> String {
+    format!("{}\n\n", fill(&options.templates.header, &session_vars(session)))
+}
+
+fn write_blocks(out: &mut String, session: &Session, blocks: &[Block], options: &RenderOptions) {
+    for block in blocks {
+        let (start, end, duration, entries) = match block {
+            Block::Mark(marker) => {
+                out.push_str(&format!("{}\n\n", format_marker(marker)));
+                continue;
+            }
+            Block::Section { start, end, duration, entries } => (start, end, duration, entries),
         };
-        let ts = format_time(*start);
-        writeln!(file, "> **{label}** ({ts}): {}\n", text.trim())?;
+
+        let mut vars = session_vars(session);
+        vars.extend([
+            ("start", format_timestamp(start)),
+            ("end", format_timestamp(end)),
+            ("duration", format_time(*duration)),
+        ]);
+        // --no-timestamps drops the built-in heading, not a user's
+        if options.timestamps || options.templates.custom_chunk() {
+            out.push_str(&format!("{}\n\n", fill(&options.templates.chunk, &vars)));
+        }
+        for entry in entries {
+            match entry {
+                Entry::Turn(speaker, offset, text) => {
+                    let mut vars = vars.clone();
+                    let time = (clock_secs(start) + offset) as u64 % 86400;
+                    vars.extend([
+                        ("speaker", speaker_label(speaker).to_string()),
+                        ("offset", if options.timestamps { format_time(*offset) } else { String::new() }),
+                        ("time", format!("{:02}:{:02}:{:02}", time / 3600, time / 60 % 60, time % 60)),
+                        ("text", text.trim().to_string()),
+                    ]);
+                    out.push_str(&format!("{}\n\n", fill(&options.templates.turn, &vars)));
+                }
+                Entry::Mark(marker) => out.push_str(&format!("{}\n\n", format_marker(marker))),
+            }
+        }
+        if options.separators {
+            out.push_str("---\n\n");
+        }
     }
+}
+
+/// "14-32-05" → seconds since midnight.
+pub(crate) fn clock_secs(ts: &str) -> f64 {
+    ts.split('-').filter_map(|n| n.parse::<f64>().ok()).fold(0.0, |acc, n| acc * 60.0 + n)
+}
+
+fn append_markdown(
+    path: &PathBuf,
+    session: &Session,
+    result: &ChunkResult,
+    options: &RenderOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let is_new = !path.exists() || fs::metadata(path).map_or(true, |m| m.len() == 0);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
 
-    writeln!(file, "---\n")?;
+    let mut out = if is_new { header(session, options) } else { String::new() };
+    write_blocks(&mut out, session, &blocks(std::slice::from_ref(result), options), options);
+    file.write_all(out.as_bytes())?;
     Ok(())
 }
 
@@ -238,3 +762,65 @@ pub fn shutdown(handles: Vec<JoinHandle<()>>) {
         let _ = h.join();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionMeta;
+    use std::sync::mpsc::sync_channel;
+
+    #[test]
+    fn failed_chunk_keeps_its_markers() {
+        let output_dir = std::env::temp_dir().join(format!("scribe-test-{}-markers", std::process::id()));
+        let session = Session::create(output_dir.to_str().unwrap(), SessionMeta {
+            mode: "live".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let transcript_path = session.dir.join("transcript.md");
+        let status = Arc::new(Status::new());
+
+        let (tx, rx) = sync_channel(1);
+        let handles = run(rx, PipelineConfig {
+            transcribe: TranscribeConfig::default(),
+            session: session.clone(),
+            transcript_path: transcript_path.clone(),
+            concurrency: 1,
+            save_audio: true,
+            audio_format: AudioFormat::Wav,
+            status: Arc::clone(&status),
+            markdown: RenderOptions::default(),
+            results_tx: None,
+        });
+        // The track's audio is missing, so transcription fails
+        tx.send(ChunkSet {
+            timestamp: "14-30-00".to_string(),
+            timestamp_end: Some("14-30-30".to_string()),
+            name: None,
+            tracks: vec![Track {
+                label: "mic".to_string(),
+                speaker: "you".to_string(),
+                source: Source::Mic,
+                path: session.dir.join("missing.wav"),
+                channel: None,
+            }],
+            markers: vec![
+                Marker::Paused { from: "14-29-00".to_string(), to: "14-30-00".to_string() },
+                Marker::Bookmark { time: "14-30-10".to_string(), offset: 10.0, note: Some("decision".to_string()) },
+            ],
+            bytes: 0,
+        })
+        .unwrap();
+        drop(tx);
+        shutdown(handles);
+
+        assert_eq!(status.failed.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let chunks = session.chunks().unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].timestamp_start, "14-30-00");
+        assert_eq!(chunks[0].markers.len(), 2);
+        let markdown = fs::read_to_string(&transcript_path).unwrap();
+        assert!(markdown.contains("decision"), "{markdown}");
+        fs::remove_dir_all(&output_dir).ok();
+    }
+}
@@ -0,0 +1,201 @@
+use crate::chunker::local_timestamp;
+use crate::pipeline::{self, ChunkResult};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Descriptive metadata supplied when a session is started.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SessionMeta {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub mode: String,
+    pub backend: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// The recording a `scribe transcribe` session was made from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+}
+
+/// One recording run. Everything it produces lives under
+/// `{output_dir}/sessions/{id}/`, described by `session.json`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Session {
+    pub id: String,
+    pub started_at: String,
+    #[serde(default)]
+    pub ended_at: Option<String>,
+    #[serde(flatten)]
+    pub meta: SessionMeta,
+    #[serde(default)]
+    pub transcript_path: Option<PathBuf>,
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+/// Local wall-clock time as "2026-02-15T14:30:05".
+pub(crate) fn local_datetime() -> String {
+    let (date, time) = local_timestamp();
+    format!("{date}T{}", time.replace('-', ":"))
+}
+
+fn sessions_root(output_dir: &str) -> PathBuf {
+    PathBuf::from(output_dir).join("sessions")
+}
+
+impl Session {
+    /// Allocate a new session ID and directory, and write the initial `session.json`.
+    pub fn create(output_dir: &str, meta: SessionMeta) -> Result<Self, Box<dyn std::error::Error>> {
+        let root = sessions_root(output_dir);
+        fs::create_dir_all(&root)?;
+
+        // IDs are the start time, suffixed if two sessions start in the same second
+        let (date, time) = local_timestamp();
+        let base = format!("{date}_{time}");
+        let mut id = base.clone();
+        let mut n = 2;
+        while root.join(&id).exists() {
+            id = format!("{base}-{n}");
+            n += 1;
+        }
+
+        Self::create_with_id(&root, id, meta)
+    }
+
+    fn create_with_id(root: &Path, id: String, meta: SessionMeta) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = root.join(&id);
+        fs::create_dir_all(&dir)?;
+
+        let session = Session {
+            id,
+            started_at: local_datetime(),
+            ended_at: None,
+            meta,
+            transcript_path: None,
+            dir,
+        };
+        session.save()?;
+        Ok(session)
+    }
+
+    /// The session with this ID, created with `meta` if there isn't one yet.
+    /// For sessions built up over several runs, like backfills.
+    pub fn open_or_create(output_dir: &str, id: &str, meta: SessionMeta) -> Result<Self, Box<dyn std::error::Error>> {
+        let root = sessions_root(output_dir);
+        if root.join(id).join("session.json").exists() {
+            return Self::load(&root.join(id));
+        }
+        fs::create_dir_all(&root)?;
+        Self::create_with_id(&root, id.to_string(), meta)
+    }
+
+    /// Load a session from its directory.
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = fs::read_to_string(dir.join("session.json"))?;
+        let mut session: Session = serde_json::from_str(&json)?;
+        session.dir = dir.to_path_buf();
+        Ok(session)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(self.dir.join("session.json"), json)?;
+        Ok(())
+    }
+
+    /// Record the end time. Sessions without one were interrupted.
+    pub fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.ended_at = Some(local_datetime());
+        self.save()
+    }
+
+    pub fn audio_dir(&self) -> PathBuf {
+        self.dir.join("audio")
+    }
+
+    pub fn chunks_dir(&self) -> PathBuf {
+        self.dir.join("chunks")
+    }
+
+    pub fn jsonl_path(&self) -> PathBuf {
+        self.dir.join("session.jsonl")
+    }
+
+    pub fn date(&self) -> &str {
+        self.started_at.split('T').next().unwrap_or(&self.started_at)
+    }
+
+    /// Chunk results from `session.jsonl`, in recording order.
+    /// Workers finish out of order, so lines are sorted by chunk start,
+    /// counted from the start of the session so chunks after midnight come
+    /// last. Chunks starting together keep their order in the file.
+    pub fn chunks(&self) -> Result<Vec<ChunkResult>, Box<dyn std::error::Error>> {
+        let text = fs::read_to_string(self.jsonl_path())?;
+        let mut chunks = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let chunk: ChunkResult = serde_json::from_str(line)
+                .map_err(|e| format!("session.jsonl line {}: {e}", i + 1))?;
+            chunks.push(chunk);
+        }
+        // Chunks of a transcribed file are timed from the start of the file
+        let origin = if self.meta.mode == "file" {
+            0.0
+        } else {
+            // A minute's slack for a first chunk stamped before the session
+            let time = self.started_at.split('T').nth(1).unwrap_or_default();
+            pipeline::clock_secs(&time.replace(':', "-")) - 60.0
+        };
+        let elapsed = |c: &ChunkResult| (pipeline::clock_secs(&c.timestamp_start) - origin).rem_euclid(86400.0);
+        chunks.sort_by(|a, b| elapsed(a).total_cmp(&elapsed(b)));
+        Ok(chunks)
+    }
+
+    /// Heading used at the top of the markdown transcript.
+    pub fn heading(&self) -> String {
+        match &self.meta.title {
+            Some(title) => format!("{title} — {}", self.date()),
+            None => format!("Transcript — {}", self.date()),
+        }
+    }
+}
+
+/// All sessions under `output_dir`, oldest first.
+pub fn list(output_dir: &str) -> Vec<Session> {
+    let Ok(entries) = fs::read_dir(sessions_root(output_dir)) else {
+        return Vec::new();
+    };
+
+    let mut sessions: Vec<Session> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| Session::load(&e.path()).ok())
+        .collect();
+    sessions.sort_by(|a, b| a.id.cmp(&b.id));
+    sessions
+}
+
+/// Resolve a session by exact ID, unique ID prefix, or directory path.
+pub fn find(output_dir: &str, query: &str) -> Result<Session, Box<dyn std::error::Error>> {
+    let path = Path::new(query);
+    if path.join("session.json").exists() {
+        return Session::load(path);
+    }
+
+    let sessions = list(output_dir);
+    if let Some(s) = sessions.iter().find(|s| s.id == query) {
+        return Ok(s.clone());
+    }
+
+    let matches: Vec<&Session> = sessions.iter().filter(|s| s.id.starts_with(query)).collect();
+    match matches.as_slice() {
+        [s] => Ok((*s).clone()),
+        [] => Err(format!("no session matching '{query}'").into()),
+        _ => Err(format!("'{query}' matches {} sessions", matches.len()).into()),
+    }
+}
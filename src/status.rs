@@ -0,0 +1,185 @@
+use crate::chunker::local_timestamp;
+use crate::pipeline::Marker;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Lines of transcript kept for live display.
+const RECENT_LINES: usize = 200;
+
+#[derive(Clone)]
+pub struct Meter {
+    pub label: String,
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// Live state shared between the chunker, pipeline workers and the UI.
+pub struct Status {
+    pub paused: AtomicBool,
+    pub chunks_flushed: AtomicUsize,
+    pub queued: AtomicUsize,
+    pub in_flight: AtomicUsize,
+    pub done: AtomicUsize,
+    pub failed: AtomicUsize,
+    /// Bytes of chunk audio queued or in flight.
+    pub pending_bytes: AtomicU64,
+    /// Chunks not handed to the pipeline because it was backlogged.
+    pub dropped: AtomicUsize,
+    /// Backlogged past the limits, so no longer transcribing.
+    pub capture_only: AtomicBool,
+    /// Capture blocks lost because the chunker fell behind.
+    pub overruns: AtomicUsize,
+    started: Instant,
+    chunk_started: Mutex<Instant>,
+    meters: Mutex<Vec<Meter>>,
+    recent: Mutex<VecDeque<String>>,
+    bookmarks: Mutex<Vec<String>>,
+    markers: Mutex<Vec<(Instant, Marker)>>,
+}
+
+impl Status {
+    pub fn new() -> Self {
+        Self {
+            paused: AtomicBool::new(false),
+            chunks_flushed: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            done: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            pending_bytes: AtomicU64::new(0),
+            dropped: AtomicUsize::new(0),
+            capture_only: AtomicBool::new(false),
+            overruns: AtomicUsize::new(0),
+            started: Instant::now(),
+            chunk_started: Mutex::new(Instant::now()),
+            meters: Mutex::new(Vec::new()),
+            recent: Mutex::new(VecDeque::new()),
+            bookmarks: Mutex::new(Vec::new()),
+            markers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn toggle_pause(&self) {
+        self.paused.fetch_xor(true, Ordering::SeqCst);
+    }
+
+    pub fn elapsed_secs(&self) -> f32 {
+        self.started.elapsed().as_secs_f32()
+    }
+
+    /// Update the level meter for `label` from a block of captured samples.
+    pub fn update_level(&self, label: &str, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+        let sum_sq: f32 = samples.iter().map(|s| s * s).sum();
+        let rms = (sum_sq / samples.len() as f32).sqrt();
+        let peak = samples.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
+
+        let mut meters = self.meters.lock().unwrap();
+        match meters.iter_mut().find(|m| m.label == label) {
+            Some(m) => {
+                m.rms = rms;
+                m.peak = peak;
+            }
+            None => meters.push(Meter { label: label.to_string(), rms, peak }),
+        }
+    }
+
+    pub fn meters(&self) -> Vec<Meter> {
+        self.meters.lock().unwrap().clone()
+    }
+
+    pub fn chunk_flushed(&self) {
+        self.chunks_flushed.fetch_add(1, Ordering::SeqCst);
+        *self.chunk_started.lock().unwrap() = Instant::now();
+    }
+
+    pub fn current_chunk_secs(&self) -> f32 {
+        self.chunk_started.lock().unwrap().elapsed().as_secs_f32()
+    }
+
+    /// A chunk of `bytes` on disk is being handed to the pipeline. Counted
+    /// before the send, since a worker can pick it up straight away.
+    pub fn chunk_queued(&self, bytes: u64) {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        self.pending_bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    /// Undo `chunk_queued` for a chunk the pipeline didn't take.
+    pub fn chunk_unqueued(&self, bytes: u64) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        self.pending_bytes.fetch_sub(bytes, Ordering::SeqCst);
+    }
+
+    /// A worker picked up a queued chunk.
+    pub fn chunk_started(&self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn chunk_finished(&self, ok: bool, bytes: u64) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.pending_bytes.fetch_sub(bytes, Ordering::SeqCst);
+        if ok {
+            self.done.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.failed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub fn push_transcript(&self, line: String) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_back(line);
+        while recent.len() > RECENT_LINES {
+            recent.pop_front();
+        }
+    }
+
+    /// The last `n` transcript lines, oldest first.
+    pub fn recent_transcript(&self, n: usize) -> Vec<String> {
+        let recent = self.recent.lock().unwrap();
+        recent.iter().skip(recent.len().saturating_sub(n)).cloned().collect()
+    }
+
+    /// Bookmark the current moment. Returns the wall-clock time ("14:32:05").
+    pub fn add_bookmark(&self, note: Option<String>) -> String {
+        let (_, time) = local_timestamp();
+        let display = time.replace('-', ":");
+        let entry = match &note {
+            Some(n) => format!("{display} {n}"),
+            None => display.clone(),
+        };
+        self.bookmarks.lock().unwrap().push(entry);
+        self.push_marker(Marker::Bookmark { time, offset: 0.0, note });
+        display
+    }
+
+    pub fn bookmarks(&self) -> Vec<String> {
+        self.bookmarks.lock().unwrap().clone()
+    }
+
+    /// Queue a marker to ride along with the next chunk sent to the pipeline.
+    /// The instant lets the chunker work out where in the chunk it falls.
+    pub fn push_marker(&self, marker: Marker) {
+        self.markers.lock().unwrap().push((Instant::now(), marker));
+    }
+
+    pub fn take_markers(&self) -> Vec<(Instant, Marker)> {
+        std::mem::take(&mut *self.markers.lock().unwrap())
+    }
+}
@@ -0,0 +1,463 @@
+use crate::pipeline::{self, ChunkResult};
+use crate::session::Session;
+use crate::transcribe::send_with_retry;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::fs;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct SummaryConfig {
+    pub api_key: String,
+    pub api_url: String,
+    pub model: String,
+    /// Per request. A local model summarizing a long meeting can take
+    /// minutes.
+    pub timeout: Duration,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        Self {
+            api_key: String::new(),
+            api_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ActionItem {
+    #[serde(default)]
+    pub owner: Option<String>,
+    pub task: String,
+    #[serde(default)]
+    pub due: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Summary {
+    #[serde(default)]
+    pub overview: String,
+    #[serde(default)]
+    pub decisions: Vec<String>,
+    #[serde(default)]
+    pub action_items: Vec<ActionItem>,
+    #[serde(default)]
+    pub open_questions: Vec<String>,
+}
+
+const SYSTEM_PROMPT: &str = "You summarize meeting transcripts. \
+Speakers are labelled at the start of each line; bracketed lines are markers \
+(bookmarks with notes, pauses) that the participants added on purpose. \
+Reply with a JSON object with exactly these keys:\n\
+- \"overview\": a short paragraph on what the meeting covered\n\
+- \"decisions\": array of strings, decisions that were made\n\
+- \"action_items\": array of {\"owner\", \"task\", \"due\"}. \"owner\" is the speaker label \
+of whoever took the task (null if unclear); \"due\" is null unless a deadline was stated\n\
+- \"open_questions\": array of strings, questions raised but not resolved\n\
+Use only what is in the transcript. Use empty arrays when there is nothing to report.";
+
+/// Flatten chunk results into "Speaker: text" lines with chunk times and markers.
+pub fn transcript_text(chunks: &[ChunkResult]) -> String {
+    let mut out = String::new();
+    for chunk in chunks {
+        if !chunk.segments.is_empty() {
+            let _ = writeln!(out, "[{}]", pipeline::format_timestamp(&chunk.timestamp_start));
+        }
+
+        let mut markers: Vec<_> = chunk.markers.iter().collect();
+        markers.sort_by(|a, b| a.offset().partial_cmp(&b.offset()).unwrap());
+        let mut markers = markers.into_iter().peekable();
+
+        for seg in &chunk.segments {
+            while let Some(m) = markers.next_if(|m| m.offset() < seg.start) {
+                let _ = writeln!(out, "{}", pipeline::format_marker(m));
+            }
+            let _ = writeln!(out, "{}: {}", pipeline::speaker_label(&seg.speaker), seg.text.trim());
+        }
+        for m in markers {
+            let _ = writeln!(out, "{}", pipeline::format_marker(m));
+        }
+    }
+    out
+}
+
+/// Ask the chat endpoint for a structured summary. `system` is the
+/// instruction prompt, `user` the transcript (plus any prior context).
+pub fn request_summary(
+    system: &str,
+    user: &str,
+    config: &SummaryConfig,
+) -> Result<Summary, Box<dyn std::error::Error>> {
+    let client = reqwest::blocking::Client::builder().timeout(config.timeout).build()?;
+    let body = json!({
+        "model": config.model,
+        "response_format": { "type": "json_object" },
+        "messages": [
+            { "role": "system", "content": system },
+            { "role": "user", "content": user },
+        ],
+    });
+
+    let resp = send_with_retry(|| {
+        let req = client.post(&config.api_url).json(&body);
+        Ok(if config.api_key.is_empty() { req } else { req.bearer_auth(&config.api_key) })
+    })?;
+
+    let reply: Value = resp.json()?;
+    let content = reply["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or("completion response has no message content")?;
+
+    // Some servers wrap JSON mode output in a code fence anyway
+    let content = content
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```");
+    serde_json::from_str(content).map_err(|e| format!("summary is not valid JSON: {e}").into())
+}
+
+pub fn to_markdown(summary: &Summary, heading: &str) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Summary — {heading}\n");
+    if !summary.overview.is_empty() {
+        let _ = writeln!(out, "{}\n", summary.overview.trim());
+    }
+
+    if !summary.decisions.is_empty() {
+        let _ = writeln!(out, "## Decisions\n");
+        for d in &summary.decisions {
+            let _ = writeln!(out, "- {d}");
+        }
+        out.push('\n');
+    }
+
+    if !summary.action_items.is_empty() {
+        let _ = writeln!(out, "## Action items\n");
+        for item in &summary.action_items {
+            let owner = item.owner.as_deref().map(|o| format!("**{o}**: ")).unwrap_or_default();
+            let due = item.due.as_deref().map(|d| format!(" (due {d})")).unwrap_or_default();
+            let _ = writeln!(out, "- [ ] {owner}{}{due}", item.task);
+        }
+        out.push('\n');
+    }
+
+    if !summary.open_questions.is_empty() {
+        let _ = writeln!(out, "## Open questions\n");
+        for q in &summary.open_questions {
+            let _ = writeln!(out, "- {q}");
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Summarize a finished session, writing `summary.json` and `summary.md`
+/// into the session directory next to `session.jsonl`.
+pub fn summarize_session(
+    session: &Session,
+    config: &SummaryConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let chunks = session.chunks()?;
+    let text = transcript_text(&chunks);
+    if text.trim().is_empty() {
+        eprintln!("Nothing transcribed — skipping summary");
+        return Ok(());
+    }
+
+    eprintln!("Summarizing session {}...", session.id);
+    let summary = request_summary(SYSTEM_PROMPT, &text, config)?;
+
+    let json_path = session.dir.join("summary.json");
+    fs::write(&json_path, serde_json::to_string_pretty(&summary)?)?;
+    let md_path = session.dir.join("summary.md");
+    fs::write(&md_path, to_markdown(&summary, &session.heading()))?;
+
+    eprintln!("Summary: {}", md_path.display());
+    Ok(())
+}
+
+/// Cap on new transcript sent per rolling-summary request, so prompts stay
+/// bounded however far behind the summarizer falls.
+const FOLD_CHARS: usize = 24_000;
+
+const ROLLING_PROMPT: &str = "You maintain a running summary of a meeting that is still in progress. \
+You are given the summary so far as JSON and the next part of the transcript. \
+Speakers are labelled at the start of each line; bracketed lines are markers \
+(bookmarks with notes, pauses). Return the updated summary as a JSON object with the \
+same keys: \"overview\", \"decisions\", \"action_items\" (each {\"owner\", \"task\", \"due\"}, \
+owner being a speaker label or null), \"open_questions\". Keep earlier points unless the new \
+transcript changes them, drop questions that have since been answered, and keep the \
+overview under 200 words so someone joining late can catch up quickly.";
+
+/// Start the rolling-summary stage. Every `every` chunks received on the
+/// returned sender are folded into `rolling-summary.{md,json}` in the
+/// session directory. Drains the remainder and exits when the sender closes.
+pub fn spawn_rolling(
+    session: Session,
+    config: SummaryConfig,
+    every: usize,
+) -> (Sender<ChunkResult>, JoinHandle<()>) {
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let mut summary = Summary::default();
+        let mut pending: Vec<ChunkResult> = Vec::new();
+        // Chunks since the last attempt; a failed one is retried with the
+        // next interval's chunks, not with every chunk after it
+        let mut since = 0;
+
+        for chunk in rx {
+            pending.push(chunk);
+            since += 1;
+            if since >= every.max(1) {
+                since = 0;
+                fold(&mut summary, &mut pending, &session, &config);
+            }
+        }
+        if !pending.is_empty() {
+            fold(&mut summary, &mut pending, &session, &config);
+        }
+    });
+    (tx, handle)
+}
+
+/// Fold `pending` into `summary`, at most `FOLD_CHARS` of transcript per
+/// request. Chunks not folded because a request failed stay in `pending`
+/// for the next try.
+fn fold(summary: &mut Summary, pending: &mut Vec<ChunkResult>, session: &Session, config: &SummaryConfig) {
+    // Workers finish out of order; fold in recording order
+    pending.sort_by(|a, b| a.timestamp_start.cmp(&b.timestamp_start));
+
+    let mut folded = 0;
+    while folded < pending.len() {
+        // Whole chunks up to the size cap; a bigger one goes alone
+        let mut end = folded;
+        let mut text = String::new();
+        while end < pending.len() {
+            let next = transcript_text(&pending[end..=end]);
+            if end > folded && text.len() + next.len() > FOLD_CHARS {
+                break;
+            }
+            text.push_str(&next);
+            end += 1;
+        }
+
+        if !text.trim().is_empty() {
+            let so_far = serde_json::to_string(&*summary).unwrap_or_default();
+            let user = format!("Summary so far:\n{so_far}\n\nNext part of the transcript:\n{text}");
+            match request_summary(ROLLING_PROMPT, &user, config) {
+                Ok(updated) => *summary = updated,
+                Err(e) => {
+                    eprintln!("Rolling summary failed: {e}");
+                    break;
+                }
+            }
+        }
+        folded = end;
+    }
+    if folded == 0 {
+        return;
+    }
+    let through = pending[folded - 1].timestamp_end.clone();
+    pending.drain(..folded);
+
+    let heading = format!(
+        "{} (running, through {})",
+        session.heading(),
+        pipeline::format_timestamp(&through)
+    );
+    let json = serde_json::to_string_pretty(&*summary).unwrap_or_default();
+    let written = fs::write(session.dir.join("rolling-summary.json"), json)
+        .and_then(|_| fs::write(session.dir.join("rolling-summary.md"), to_markdown(summary, &heading)));
+    match written {
+        Ok(()) => eprintln!("  rolling summary updated (through {})", pipeline::format_timestamp(&through)),
+        Err(e) => eprintln!("Rolling summary write failed: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Marker;
+    use crate::session::SessionMeta;
+    use crate::transcribe::SpeakerSegment;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn segment(speaker: &str, start: f64, text: &str) -> SpeakerSegment {
+        SpeakerSegment {
+            speaker: speaker.to_string(),
+            start,
+            end: start + 2.0,
+            text: text.to_string(),
+            words: Vec::new(),
+            avg_logprob: None,
+            no_speech_prob: None,
+        }
+    }
+
+    fn chunk(start: &str, segments: Vec<SpeakerSegment>, markers: Vec<Marker>) -> ChunkResult {
+        ChunkResult {
+            timestamp_start: start.to_string(),
+            timestamp_end: start.to_string(),
+            duration_seconds: 30.0,
+            segments,
+            audio_files: None,
+            languages: None,
+            markers,
+            dropped: Vec::new(),
+        }
+    }
+
+    /// Answer a request on a local port for each of `contents` in turn,
+    /// handing back the request bodies. Later requests fail.
+    fn mock_completion(contents: &[&str]) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+        let replies: Vec<String> = contents
+            .iter()
+            .map(|content| json!({ "choices": [{ "message": { "role": "assistant", "content": content } }] }).to_string())
+            .collect();
+        let handle = thread::spawn(move || replies.into_iter().map(|reply| answer(&listener, &reply)).collect());
+        (url, handle)
+    }
+
+    /// Read one request and reply with `reply`; returns the request body.
+    fn answer(listener: &TcpListener, reply: &str) -> String {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + length {
+                    break;
+                }
+            }
+            if n == 0 {
+                break;
+            }
+        }
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{reply}",
+            reply.len()
+        )
+        .unwrap();
+        let text = String::from_utf8_lossy(&request).to_string();
+        text.split_once("\r\n\r\n").map(|(_, body)| body.to_string()).unwrap_or_default()
+    }
+
+    /// A fresh session to fold into, and chunks at 14:30, 14:31 and 14:32
+    /// of `chars` characters of speech each.
+    fn rolling_fixture(name: &str, chars: usize) -> (Session, Vec<ChunkResult>) {
+        let output_dir = std::env::temp_dir().join(format!("scribe-test-{}-{name}", std::process::id()));
+        let session = Session::create(output_dir.to_str().unwrap(), SessionMeta {
+            mode: "live".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let chunks = ["14-30-00", "14-31-00", "14-32-00"]
+            .iter()
+            .map(|start| chunk(start, vec![segment("you", 0.0, &"word ".repeat(chars / 5))], Vec::new()))
+            .collect();
+        (session, chunks)
+    }
+
+    #[test]
+    fn transcript_text_interleaves_markers() {
+        let chunks = [chunk(
+            "14-30-00",
+            vec![segment("you", 0.0, " Let's start."), segment("other", 5.0, " Sure.")],
+            vec![Marker::Bookmark { time: "14-30-03".into(), offset: 3.0, note: Some("budget".into()) }],
+        )];
+        assert_eq!(
+            transcript_text(&chunks),
+            "[14:30:00]\nYou: Let's start.\n[bookmark 14:30] budget\nOther: Sure.\n"
+        );
+    }
+
+    #[test]
+    fn request_summary_parses_fenced_json() {
+        let content = "```json\n{\"overview\": \"Planned the release.\", \"decisions\": [\"Ship Friday\"], \
+            \"action_items\": [{\"owner\": \"You\", \"task\": \"Tag the build\", \"due\": null}]}\n```";
+        let (url, server) = mock_completion(&[content]);
+        let config = SummaryConfig { api_url: url, model: "test-model".into(), ..Default::default() };
+
+        let summary = request_summary(SYSTEM_PROMPT, "You: ship it", &config).unwrap();
+        assert_eq!(summary.overview, "Planned the release.");
+        assert_eq!(summary.decisions, ["Ship Friday"]);
+        assert_eq!(summary.action_items[0].owner.as_deref(), Some("You"));
+        assert!(summary.open_questions.is_empty());
+
+        let request: Value = serde_json::from_str(&server.join().unwrap()[0]).unwrap();
+        assert_eq!(request["model"], "test-model");
+        assert_eq!(request["messages"][1]["content"], "You: ship it");
+    }
+
+    #[test]
+    fn markdown_sections() {
+        let summary = Summary {
+            overview: "Planned the release.".into(),
+            decisions: vec!["Ship Friday".into()],
+            action_items: vec![ActionItem { owner: Some("You".into()), task: "Tag the build".into(), due: Some("Thursday".into()) }],
+            open_questions: Vec::new(),
+        };
+        assert_eq!(
+            to_markdown(&summary, "Standup"),
+            "# Summary — Standup\n\nPlanned the release.\n\n## Decisions\n\n- Ship Friday\n\n\
+             ## Action items\n\n- [ ] **You**: Tag the build (due Thursday)\n\n"
+        );
+    }
+
+    #[test]
+    fn fold_batches_at_fold_chars() {
+        // Two chunks fit under FOLD_CHARS, the third goes in a second request
+        let (session, mut pending) = rolling_fixture("fold-batches", FOLD_CHARS * 2 / 5);
+        let (url, server) = mock_completion(&[r#"{"overview": "First part."}"#, r#"{"overview": "All of it."}"#]);
+        let config = SummaryConfig { api_url: url, ..Default::default() };
+
+        let mut summary = Summary::default();
+        fold(&mut summary, &mut pending, &session, &config);
+        assert!(pending.is_empty());
+        assert_eq!(summary.overview, "All of it.");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("[14:30:00]") && requests[0].contains("[14:31:00]"));
+        assert!(!requests[0].contains("[14:32:00]"));
+        assert!(requests[1].contains("[14:32:00]") && requests[1].contains("First part."));
+        let markdown = fs::read_to_string(session.dir.join("rolling-summary.md")).unwrap();
+        assert!(markdown.contains("All of it."));
+        fs::remove_dir_all(session.dir.parent().unwrap().parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn fold_keeps_chunks_after_a_failure() {
+        // The second request finds no server
+        let (session, mut pending) = rolling_fixture("fold-failure", FOLD_CHARS * 2 / 5);
+        let (url, server) = mock_completion(&[r#"{"overview": "First part."}"#]);
+        let config = SummaryConfig { api_url: url, timeout: Duration::from_secs(5), ..Default::default() };
+
+        let mut summary = Summary::default();
+        fold(&mut summary, &mut pending, &session, &config);
+        server.join().unwrap();
+        assert_eq!(summary.overview, "First part.");
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].timestamp_start, "14-32-00");
+        fs::remove_dir_all(session.dir.parent().unwrap().parent().unwrap()).ok();
+    }
+}
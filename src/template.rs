@@ -0,0 +1,165 @@
+//! User templates for the markdown transcript, so its layout can follow a
+//! team's note conventions (Obsidian front matter and the like).
+
+use std::fs;
+use std::path::Path;
+
+/// Templates for the three parts of a transcript. Each part is followed by
+/// a blank line.
+#[derive(Clone)]
+pub struct Templates {
+    /// Top of the file: `{{heading}}`, `{{title}}`, `{{date}}`, `{{id}}`,
+    /// `{{started}}`, `{{ended}}`, `{{tags}}`, `{{mode}}`, `{{backend}}`,
+    /// `{{model}}`.
+    pub header: String,
+    /// Before each chunk's turns: the header variables plus `{{start}}`,
+    /// `{{end}}` and `{{duration}}`.
+    pub chunk: String,
+    /// Each speaker turn: the chunk variables plus `{{speaker}}`,
+    /// `{{offset}}` (into the chunk), `{{time}}` (of day) and `{{text}}`.
+    pub turn: String,
+}
+
+const DEFAULT_CHUNK: &str = "## {{start}} — {{end}} ({{duration}})";
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            header: "# {{heading}}".into(),
+            chunk: DEFAULT_CHUNK.into(),
+            turn: "> **{{speaker}}**{{#if offset}} ({{offset}}){{/if}}: {{text}}".into(),
+        }
+    }
+}
+
+impl Templates {
+    /// Whether `chunk` is the user's rather than the built-in heading.
+    pub fn custom_chunk(&self) -> bool {
+        self.chunk != DEFAULT_CHUNK
+    }
+
+    /// `header.md`, `chunk.md` and `turn.md` from `dir`. Missing files keep
+    /// the default for that part.
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if !dir.is_dir() {
+            return Err(format!("{}: not a directory", dir.display()).into());
+        }
+        let mut templates = Self::default();
+        for (name, template) in [
+            ("header.md", &mut templates.header),
+            ("chunk.md", &mut templates.chunk),
+            ("turn.md", &mut templates.turn),
+        ] {
+            let path = dir.join(name);
+            if path.exists() {
+                let text = fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+                *template = text.trim_end_matches(['\n', '\r']).to_string();
+            }
+        }
+        Ok(templates)
+    }
+}
+
+/// Expand `template`: `{{name}}` becomes the variable's value as is, or
+/// nothing if there's no such variable; `{{name | yaml}}` quotes it as a
+/// YAML string for front matter. Values are never expanded themselves, so
+/// speech containing `{{` comes out unchanged. `{{#if name}}…{{/if}}` keeps
+/// its contents only when `name` is non-empty; these don't nest.
+pub fn fill(template: &str, vars: &[(&str, &str)]) -> String {
+    let get = |name: &str| vars.iter().find(|(k, _)| *k == name).map_or("", |(_, v)| *v);
+
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        out.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find("}}") else {
+            rest = &rest[open..];
+            break;
+        };
+        let tag = rest[open + 2..open + close].trim();
+        rest = &rest[open + close + 2..];
+
+        if let Some(name) = tag.strip_prefix("#if ") {
+            let (body, after) = rest.split_once("{{/if}}").unwrap_or((rest, ""));
+            if !get(name.trim()).is_empty() {
+                out.push_str(&fill(body, vars));
+            }
+            rest = after;
+        } else if let Some((name, filter)) = tag.split_once('|') {
+            match filter.trim() {
+                "yaml" => out.push_str(&yaml_string(get(name.trim()))),
+                _ => out.push_str(get(name.trim())),
+            }
+        } else {
+            out.push_str(get(tag));
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `value` as a double-quoted YAML scalar.
+fn yaml_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables() {
+        let vars = [("speaker", "You"), ("text", "Hello")];
+        assert_eq!(fill("**{{speaker}}**: {{ text }}", &vars), "**You**: Hello");
+        assert_eq!(fill("[{{missing}}]", &vars), "[]");
+    }
+
+    #[test]
+    fn if_blocks() {
+        let template = "> **{{speaker}}**{{#if offset}} ({{offset}}){{/if}}: {{text}}";
+        let with = [("speaker", "You"), ("offset", "3s"), ("text", "Hi")];
+        let without = [("speaker", "You"), ("offset", ""), ("text", "Hi")];
+        assert_eq!(fill(template, &with), "> **You** (3s): Hi");
+        assert_eq!(fill(template, &without), "> **You**: Hi");
+        assert_eq!(fill("a{{#if nope}}b", &with), "a");
+    }
+
+    #[test]
+    fn values_are_not_expanded() {
+        let vars = [("text", "say {{speaker}} and {{#if x}}"), ("speaker", "You")];
+        assert_eq!(fill("{{text}}", &vars), "say {{speaker}} and {{#if x}}");
+    }
+
+    #[test]
+    fn unclosed_tag_is_literal() {
+        assert_eq!(fill("a {{speaker", &[("speaker", "You")]), "a {{speaker");
+    }
+
+    #[test]
+    fn yaml_filter() {
+        let vars = [("title", "Q3: \"Launch\" plan\\draft")];
+        assert_eq!(fill("title: {{title | yaml}}", &vars), "title: \"Q3: \\\"Launch\\\" plan\\\\draft\"");
+        assert_eq!(fill("title: {{title}}", &vars), "title: Q3: \"Launch\" plan\\draft");
+    }
+
+    #[test]
+    fn default_chunk_is_not_custom() {
+        let mut templates = Templates::default();
+        assert!(!templates.custom_chunk());
+        templates.chunk = "### {{start}}".into();
+        assert!(templates.custom_chunk());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::audio::{self, AudioFormat};
+use crate::{hallucination, vocab};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::thread;
@@ -8,6 +10,18 @@ pub struct TranscribeConfig {
     pub api_key: String,
     pub api_url: String,
     pub model: String,
+    /// Terms sent in the prompt to bias spelling of names and jargon.
+    pub glossary: Vec<String>,
+    /// Find/replace pairs applied to the returned text.
+    pub replacements: Vec<vocab::Replacement>,
+    /// Spoken language hint (ISO-639-1, e.g. "de"); auto-detected when unset.
+    pub language: Option<String>,
+    /// Translate to English instead of transcribing.
+    pub translate: bool,
+    /// Drop segments that look like Whisper hallucinations.
+    pub filter_hallucinations: bool,
+    /// WAVs are re-encoded to this before upload.
+    pub upload_format: AudioFormat,
 }
 
 impl Default for TranscribeConfig {
@@ -16,6 +30,12 @@ impl Default for TranscribeConfig {
             api_key: String::new(),
             api_url: "https://api.openai.com/v1/audio/transcriptions".to_string(),
             model: "whisper-1".to_string(),
+            glossary: Vec::new(),
+            replacements: Vec::new(),
+            language: None,
+            translate: false,
+            filter_hallucinations: true,
+            upload_format: AudioFormat::Wav,
         }
     }
 }
@@ -25,17 +45,25 @@ pub struct Word {
     pub word: String,
     pub start: f64,
     pub end: f64,
+    /// Token probability, when the server reports it (whisper.cpp does).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Transcript {
     pub text: String,
+    /// Language the model detected (or was told), as the server reports it.
+    #[serde(default)]
+    pub language: Option<String>,
     #[serde(default)]
     pub segments: Vec<Segment>,
     #[serde(default)]
     pub words: Vec<Word>,
     #[serde(default)]
     pub duration: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<DroppedSegment>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -45,75 +73,166 @@ pub struct Segment {
     pub text: String,
     #[serde(default)]
     pub words: Vec<Word>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_speech_prob: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_logprob: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression_ratio: Option<f64>,
 }
 
-#[derive(Debug, Serialize)]
+/// A segment removed as a likely hallucination, kept for auditing.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DroppedSegment {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub speaker: String,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SpeakerSegment {
     pub speaker: String,
     pub start: f64,
     pub end: f64,
     pub text: String,
     pub words: Vec<Word>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_logprob: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_speech_prob: Option<f64>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct MergedTranscript {
     pub segments: Vec<SpeakerSegment>,
     pub duration: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<DroppedSegment>,
 }
 
+/// Transcribe one file. `context` is recent text from the same channel,
+/// passed to the model along with the glossary for continuity.
 pub fn transcribe(
     path: &str,
     config: &TranscribeConfig,
+    context: Option<&str>,
 ) -> Result<Transcript, Box<dyn std::error::Error>> {
     let file_path = Path::new(path);
     if !file_path.exists() {
         return Err(format!("file not found: {path}").into());
     }
 
-    let file_bytes = std::fs::read(file_path)?;
-    let file_name = file_path
+    // Compress WAVs on the way up if asked; other files go as they are
+    let format = AudioFormat::from_path(file_path);
+    let (file_bytes, upload_path, mime) = match format {
+        Some(AudioFormat::Wav) if config.upload_format != AudioFormat::Wav => {
+            let (samples, rate, channels) = audio::read_wav_i16(file_path)?;
+            let bytes = audio::encode(&samples, rate, channels, config.upload_format)?;
+            let name = file_path.with_extension(config.upload_format.extension());
+            (bytes, name, config.upload_format.mime())
+        }
+        _ => (
+            std::fs::read(file_path)?,
+            file_path.to_path_buf(),
+            format.map_or("application/octet-stream", AudioFormat::mime),
+        ),
+    };
+    let file_name = upload_path
         .file_name()
         .unwrap_or_default()
         .to_string_lossy()
         .to_string();
 
+    let prompt = vocab::build_prompt(&config.glossary, context);
     let client = reqwest::blocking::Client::new();
-    let max_retries = 3;
-    let mut attempt = 0;
 
-    loop {
+    // OpenAI-style servers translate on a sibling endpoint that takes neither
+    // a language nor timestamp granularities; whisper.cpp takes a flag instead.
+    let translations_url = config
+        .api_url
+        .strip_suffix("/audio/transcriptions")
+        .filter(|_| config.translate)
+        .map(|base| format!("{base}/audio/translations"));
+
+    let resp = send_with_retry(|| {
         let part = reqwest::blocking::multipart::Part::bytes(file_bytes.clone())
             .file_name(file_name.clone())
-            .mime_str("audio/wav")?;
+            .mime_str(mime)?;
 
-        let form = reqwest::blocking::multipart::Form::new()
+        let mut form = reqwest::blocking::multipart::Form::new()
             .part("file", part)
             .text("model", config.model.clone())
-            .text("response_format", "verbose_json")
-            .text("timestamp_granularities[]", "word")
-            .text("timestamp_granularities[]", "segment");
+            .text("response_format", "verbose_json");
+        if translations_url.is_none() {
+            form = form
+                .text("timestamp_granularities[]", "word")
+                .text("timestamp_granularities[]", "segment");
+            if let Some(lang) = &config.language {
+                form = form.text("language", lang.clone());
+            }
+            if config.translate {
+                form = form.text("translate", "true");
+            }
+        }
+        if let Some(p) = &prompt {
+            form = form.text("prompt", p.clone());
+        }
 
-        let resp = client
-            .post(&config.api_url)
+        Ok(client
+            .post(translations_url.as_deref().unwrap_or(&config.api_url))
             .bearer_auth(&config.api_key)
-            .multipart(form)
-            .send()?;
+            .multipart(form))
+    })?;
+
+    let mut transcript: Transcript = resp.json()?;
+    // whisper.cpp nests words inside segments; OpenAI uses top-level words.
+    // Normalize: if top-level words is empty, flatten from segments.
+    if transcript.words.is_empty() {
+        transcript.words = transcript
+            .segments
+            .iter()
+            .flat_map(|s| s.words.iter().cloned())
+            .collect();
+    }
+    if config.filter_hallucinations {
+        hallucination::filter(&mut transcript);
+    }
+    if !config.replacements.is_empty() {
+        apply_replacements(&mut transcript, &config.replacements);
+    }
+    Ok(transcript)
+}
 
+fn apply_replacements(transcript: &mut Transcript, replacements: &[vocab::Replacement]) {
+    transcript.text = vocab::apply(&transcript.text, replacements);
+    for seg in &mut transcript.segments {
+        seg.text = vocab::apply(&seg.text, replacements);
+        for w in &mut seg.words {
+            w.word = vocab::apply(&w.word, replacements);
+        }
+    }
+    for w in &mut transcript.words {
+        w.word = vocab::apply(&w.word, replacements);
+    }
+}
+
+/// Send a request, retrying transient failures (429, 5xx) with exponential
+/// backoff. `build` is called once per attempt since bodies aren't reusable.
+pub(crate) fn send_with_retry(
+    build: impl Fn() -> Result<reqwest::blocking::RequestBuilder, Box<dyn std::error::Error>>,
+) -> Result<reqwest::blocking::Response, Box<dyn std::error::Error>> {
+    let max_retries = 3;
+    let mut attempt = 0;
+
+    loop {
+        let resp = build()?.send()?;
         let status = resp.status();
 
         if status.is_success() {
-            let mut transcript: Transcript = resp.json()?;
-            // whisper.cpp nests words inside segments; OpenAI uses top-level words.
-            // Normalize: if top-level words is empty, flatten from segments.
-            if transcript.words.is_empty() {
-                transcript.words = transcript
-                    .segments
-                    .iter()
-                    .flat_map(|s| s.words.iter().cloned())
-                    .collect();
-            }
-            return Ok(transcript);
+            return Ok(resp);
         }
 
         let body = resp.text().unwrap_or_default();
@@ -222,12 +341,24 @@ fn dedup_bleed(system: &Transcript, mic: &mut Transcript) {
     mic.segments.retain(|seg| !seg.text.is_empty());
 }
 
-pub fn merge_transcripts(system: Option<Transcript>, mic: Option<Transcript>) -> MergedTranscript {
-    let sys_dur = system.as_ref().map_or(0.0, |t| t.duration);
-    let mic_dur = mic.as_ref().map_or(0.0, |t| t.duration);
-    let duration = sys_dur.max(mic_dur);
+/// One input's transcript and the speaker it's attributed to.
+pub struct TrackTranscript {
+    pub speaker: String,
+    /// Mic tracks have words that bled in from the system tracks removed.
+    pub mic: bool,
+    pub transcript: Option<Transcript>,
+}
+
+pub fn merge_transcripts(tracks: Vec<TrackTranscript>) -> MergedTranscript {
+    let duration = tracks
+        .iter()
+        .filter_map(|t| t.transcript.as_ref())
+        .map(|t| t.duration)
+        .fold(0.0, f64::max);
 
-    let to_speaker_segments = |t: Transcript, speaker: &str| -> Vec<SpeakerSegment> {
+    let mut dropped = Vec::new();
+    let mut to_speaker_segments = |t: Transcript, speaker: &str| -> Vec<SpeakerSegment> {
+        dropped.extend(t.dropped.into_iter().map(|d| DroppedSegment { speaker: speaker.to_string(), ..d }));
         let words = t.words;
         t.segments
             .into_iter()
@@ -243,25 +374,35 @@ pub fn merge_transcripts(system: Option<Transcript>, mic: Option<Transcript>) ->
                     end: seg.end,
                     text: seg.text,
                     words: seg_words,
+                    avg_logprob: seg.avg_logprob,
+                    no_speech_prob: seg.no_speech_prob,
                 }
             })
             .collect()
     };
 
-    // Dedup bleed from mic before merging
-    let mut mic = mic;
-    if let (Some(sys), Some(m)) = (&system, &mut mic) {
-        dedup_bleed(sys, m);
+    // Dedup bleed from each mic track before merging
+    let mut tracks = tracks;
+    for i in 0..tracks.len() {
+        if !tracks[i].mic {
+            continue;
+        }
+        let Some(mut mic) = tracks[i].transcript.take() else { continue };
+        for sys in tracks.iter().filter(|t| !t.mic).filter_map(|t| t.transcript.as_ref()) {
+            dedup_bleed(sys, &mut mic);
+        }
+        tracks[i].transcript = Some(mic);
     }
 
     let mut segments = Vec::new();
-    if let Some(sys) = system {
-        segments.extend(to_speaker_segments(sys, "other"));
-    }
-    if let Some(mic) = mic {
-        segments.extend(to_speaker_segments(mic, "you"));
+    for track in tracks {
+        if let Some(t) = track.transcript {
+            segments.extend(to_speaker_segments(t, &track.speaker));
+        }
     }
     segments.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
 
-    MergedTranscript { segments, duration }
+    dropped.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+
+    MergedTranscript { segments, duration, dropped }
 }
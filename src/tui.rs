@@ -0,0 +1,236 @@
+use crate::status::{Meter, Status};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Meter scale floor — anything quieter draws as an empty bar.
+const METER_FLOOR_DB: f32 = -60.0;
+
+/// Points stderr at a file while the UI owns the terminal, so worker
+/// log lines don't scribble over the display. Restored on drop.
+struct StderrRedirect {
+    saved_fd: libc::c_int,
+}
+
+impl StderrRedirect {
+    fn to_file(path: &Path) -> io::Result<Self> {
+        let file = File::create(path)?;
+        unsafe {
+            let saved_fd = libc::dup(libc::STDERR_FILENO);
+            if saved_fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::dup2(file.as_raw_fd(), libc::STDERR_FILENO) < 0 {
+                let e = io::Error::last_os_error();
+                libc::close(saved_fd);
+                return Err(e);
+            }
+            Ok(Self { saved_fd })
+        }
+    }
+}
+
+impl Drop for StderrRedirect {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dup2(self.saved_fd, libc::STDERR_FILENO);
+            libc::close(self.saved_fd);
+        }
+    }
+}
+
+/// Live terminal UI. Runs on its own thread until dropped.
+pub struct Tui {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    _redirect: StderrRedirect,
+}
+
+impl Tui {
+    pub fn start(
+        status: Arc<Status>,
+        running: Arc<AtomicBool>,
+        header: String,
+        log_path: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Redirect first, so a failure leaves the terminal as it was
+        let redirect = StderrRedirect::to_file(log_path)?;
+        terminal::enable_raw_mode()?;
+        if let Err(e) = execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e.into());
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let s = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            if let Err(e) = ui_loop(&status, &running, &s, &header) {
+                eprintln!("TUI error: {e}");
+                running.store(false, Ordering::SeqCst);
+            }
+        });
+
+        Ok(Self { stop, handle: Some(handle), _redirect: redirect })
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn ui_loop(
+    status: &Status,
+    running: &AtomicBool,
+    stop: &AtomicBool,
+    header: &str,
+) -> io::Result<()> {
+    let mut out = io::stdout();
+
+    while !stop.load(Ordering::SeqCst) {
+        draw(&mut out, status, running.load(Ordering::SeqCst), header)?;
+
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        // Raw mode swallows SIGINT, so Ctrl+C arrives as a key
+        let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            _ if ctrl_c => running.store(false, Ordering::SeqCst),
+            KeyCode::Char('q') | KeyCode::Esc => running.store(false, Ordering::SeqCst),
+            KeyCode::Char('p') | KeyCode::Char(' ') => status.toggle_pause(),
+            KeyCode::Char('b') => {
+                status.add_bookmark(None);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn format_elapsed(secs: f32) -> String {
+    let secs = secs as u32;
+    format!("{:02}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+fn to_db(level: f32) -> f32 {
+    20.0 * level.max(1e-6).log10()
+}
+
+fn meter_line(meter: &Meter, width: usize) -> String {
+    let bar_width = width.saturating_sub(36).clamp(10, 60);
+    let rms_db = to_db(meter.rms);
+    let peak_db = to_db(meter.peak);
+    let fill = |db: f32| ((1.0 - db / METER_FLOOR_DB).clamp(0.0, 1.0) * bar_width as f32) as usize;
+
+    let filled = fill(rms_db);
+    let peak_at = fill(peak_db).min(bar_width.saturating_sub(1));
+    let bar: String = (0..bar_width)
+        .map(|i| if i < filled { '█' } else if i == peak_at { '│' } else { '·' })
+        .collect();
+
+    format!("{:<7} {bar} {rms_db:>4.0} dB  peak {peak_db:>4.0} dB", meter.label)
+}
+
+/// Break `line` into pieces no wider than `width` characters.
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() || width == 0 {
+        return vec![String::new()];
+    }
+    chars.chunks(width).map(|c| c.iter().collect()).collect()
+}
+
+fn draw(out: &mut impl Write, status: &Status, running: bool, header: &str) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let width = cols as usize;
+    let rows = rows as usize;
+
+    let state = if !running {
+        "STOPPING"
+    } else if status.is_paused() {
+        "PAUSED"
+    } else {
+        "● REC"
+    };
+
+    let mut lines = vec![
+        format!("{header}  {state}  {}", format_elapsed(status.elapsed_secs())),
+        String::new(),
+    ];
+    for meter in status.meters() {
+        lines.push(meter_line(&meter, width));
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "chunks {} (current {:.1}s)   queued {}   transcribing {}   done {}   failed {}",
+        status.chunks_flushed.load(Ordering::SeqCst),
+        status.current_chunk_secs(),
+        status.queued.load(Ordering::SeqCst),
+        status.in_flight.load(Ordering::SeqCst),
+        status.done.load(Ordering::SeqCst),
+        status.failed.load(Ordering::SeqCst),
+    ));
+    lines.push(format!(
+        "pending {:.1} MB   dropped {}   overruns {}{}",
+        status.pending_bytes.load(Ordering::SeqCst) as f64 / 1_048_576.0,
+        status.dropped.load(Ordering::SeqCst),
+        status.overruns.load(Ordering::SeqCst),
+        if status.capture_only.load(Ordering::SeqCst) { "   CAPTURE ONLY" } else { "" },
+    ));
+    let bookmarks = status.bookmarks();
+    lines.push(match bookmarks.last() {
+        Some(last) => format!("bookmarks {} (last {last})", bookmarks.len()),
+        None => "bookmarks 0".to_string(),
+    });
+    lines.push("─".repeat(width));
+
+    // Fill the remaining height with the tail of the transcript
+    let available = rows.saturating_sub(lines.len() + 2);
+    let wrapped: Vec<String> = status
+        .recent_transcript(available)
+        .iter()
+        .flat_map(|l| wrap(l, width))
+        .collect();
+    let start = wrapped.len().saturating_sub(available);
+    lines.extend(wrapped[start..].iter().cloned());
+    while lines.len() < rows.saturating_sub(2) {
+        lines.push(String::new());
+    }
+    lines.push("─".repeat(width));
+    lines.push("[p] pause/resume   [b] bookmark   [q] quit".to_string());
+
+    for (row, line) in lines.iter().take(rows).enumerate() {
+        let line: String = line.chars().take(width).collect();
+        queue!(
+            out,
+            cursor::MoveTo(0, row as u16),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(line)
+        )?;
+    }
+    out.flush()
+}
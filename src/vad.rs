@@ -0,0 +1,139 @@
+//! Picks quiet places to cut a long recording, so pieces sent for
+//! transcription don't split a word.
+
+/// Energy is compared over frames this long.
+const FRAME_SECS: f64 = 0.03;
+
+/// A piece of the recording, starting `start` samples in.
+pub struct Piece {
+    pub start: usize,
+    pub samples: Vec<f32>,
+}
+
+/// Cuts mono audio, fed in as it's decoded, into pieces of at most `target`
+/// seconds. Each cut falls on the quietest frame in the last `window`
+/// seconds before the target length. Only audio not yet cut off is held.
+pub struct Splitter {
+    frame: usize,
+    target: usize,
+    window: usize,
+    pending: Vec<f32>,
+    /// Samples already cut off.
+    offset: usize,
+}
+
+impl Splitter {
+    pub fn new(rate: u32, target: f64, window: f64) -> Self {
+        let frame = ((rate as f64 * FRAME_SECS) as usize).max(1);
+        let target = ((target * rate as f64) as usize).max(frame);
+        let window = ((window * rate as f64) as usize).min(target / 2);
+        Self { frame, target, window, pending: Vec::new(), offset: 0 }
+    }
+
+    /// Add `samples`; returns the pieces that completes.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Piece> {
+        self.pending.extend_from_slice(samples);
+        let mut pieces = Vec::new();
+        while self.pending.len() > self.target {
+            let end = self.target;
+            let cut = (end - self.window..end)
+                .step_by(self.frame)
+                .min_by(|&a, &b| self.frame_energy(a).total_cmp(&self.frame_energy(b)))
+                .map_or(end, |quietest| quietest + self.frame / 2)
+                .min(end);
+            let rest = self.pending.split_off(cut);
+            pieces.push(Piece { start: self.offset, samples: std::mem::replace(&mut self.pending, rest) });
+            self.offset += cut;
+        }
+        pieces
+    }
+
+    /// The remaining audio as the last piece, if there is any.
+    pub fn finish(self) -> Option<Piece> {
+        (!self.pending.is_empty()).then_some(Piece { start: self.offset, samples: self.pending })
+    }
+
+    fn frame_energy(&self, at: usize) -> f32 {
+        energy(&self.pending[at..(at + self.frame).min(self.pending.len())])
+    }
+}
+
+fn energy(frame: &[f32]) -> f32 {
+    frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    /// Speech-like noise with a silent gap at each of `gaps` (in seconds).
+    fn speech(secs: f64, gaps: &[f64]) -> Vec<f32> {
+        let mut seed = 1u32;
+        (0..(secs * RATE as f64) as usize)
+            .map(|i| {
+                let t = i as f64 / RATE as f64;
+                if gaps.iter().any(|&g| (g..g + 0.3).contains(&t)) {
+                    return 0.0;
+                }
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    fn split(samples: &[f32], feed: usize, target: f64, window: f64) -> Vec<Piece> {
+        let mut splitter = Splitter::new(RATE, target, window);
+        let mut pieces: Vec<Piece> = samples.chunks(feed).flat_map(|s| splitter.push(s)).collect();
+        pieces.extend(splitter.finish());
+        pieces
+    }
+
+    #[test]
+    fn pieces_cover_the_audio() {
+        let samples = speech(47.3, &[]);
+        let pieces = split(&samples, samples.len(), 10.0, 3.0);
+        let mut next = 0;
+        for piece in &pieces {
+            assert_eq!(piece.start, next);
+            assert!(piece.samples.len() <= 10 * RATE as usize);
+            assert!(piece.samples.len() >= 7 * RATE as usize || piece.start + piece.samples.len() == samples.len());
+            next += piece.samples.len();
+        }
+        assert_eq!(next, samples.len());
+        let joined: Vec<f32> = pieces.into_iter().flat_map(|p| p.samples).collect();
+        assert!(joined == samples);
+    }
+
+    #[test]
+    fn cuts_fall_in_gaps() {
+        let samples = speech(25.0, &[8.5, 17.0]);
+        let pieces = split(&samples, samples.len(), 10.0, 3.0);
+        let cuts: Vec<f64> = pieces[1..].iter().map(|p| p.start as f64 / RATE as f64).collect();
+        assert_eq!(cuts.len(), 2);
+        assert!((8.5..8.8).contains(&cuts[0]), "cut at {}", cuts[0]);
+        assert!((17.0..17.3).contains(&cuts[1]), "cut at {}", cuts[1]);
+    }
+
+    #[test]
+    fn feeding_in_pieces_cuts_the_same() {
+        let samples = speech(63.0, &[12.0, 27.5, 44.0]);
+        let whole = split(&samples, samples.len(), 15.0, 5.0);
+        let fed = split(&samples, 1234, 15.0, 5.0);
+        assert_eq!(whole.len(), fed.len());
+        for (a, b) in whole.iter().zip(&fed) {
+            assert_eq!(a.start, b.start);
+            assert!(a.samples == b.samples);
+        }
+    }
+
+    #[test]
+    fn short_audio_is_one_piece() {
+        let samples = speech(3.0, &[]);
+        let pieces = split(&samples, 100, 10.0, 3.0);
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0].samples.len(), samples.len());
+        assert!(split(&[], 100, 10.0, 3.0).is_empty());
+    }
+}
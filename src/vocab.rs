@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whisper only conditions on the last ~224 tokens of the prompt.
+const MAX_PROMPT_CHARS: usize = 800;
+
+/// How much of the previous chunk's text to carry into the next prompt.
+const CONTEXT_CHARS: usize = 300;
+
+/// `(wrong, right)` — a find/replace applied to transcribed text.
+pub type Replacement = (String, String);
+
+/// Per-user vocabulary files, picked up automatically when present.
+fn config_dir() -> Option<PathBuf> {
+    let home = std::env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".config").join("scribe"))
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Glossary file: one term per line, `#` comments.
+pub fn load_glossary(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    read_lines(path)
+}
+
+/// Replacement file: one `wrong -> right` pair per line, `#` comments.
+pub fn load_replacements(path: &Path) -> Result<Vec<Replacement>, Box<dyn std::error::Error>> {
+    read_lines(path)?
+        .into_iter()
+        .map(|line| {
+            let (from, to) = line
+                .split_once("->")
+                .ok_or_else(|| format!("{}: expected 'wrong -> right', got '{line}'", path.display()))?;
+            Ok((from.trim().to_string(), to.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Glossary and replacements from `~/.config/scribe/{glossary,replacements}.txt`
+/// plus `--glossary=FILE`, `--vocab=A,B` and `--replacements=FILE`.
+pub fn from_args(
+    args: &[String],
+) -> Result<(Vec<String>, Vec<Replacement>), Box<dyn std::error::Error>> {
+    let mut glossary = Vec::new();
+    let mut replacements = Vec::new();
+
+    if let Some(dir) = config_dir() {
+        let path = dir.join("glossary.txt");
+        if path.exists() {
+            glossary.extend(load_glossary(&path)?);
+        }
+        let path = dir.join("replacements.txt");
+        if path.exists() {
+            replacements.extend(load_replacements(&path)?);
+        }
+    }
+
+    if let Some(path) = args.iter().find_map(|a| a.strip_prefix("--glossary=")) {
+        glossary.extend(load_glossary(Path::new(path))?);
+    }
+    if let Some(terms) = args.iter().find_map(|a| a.strip_prefix("--vocab=")) {
+        glossary.extend(terms.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string));
+    }
+    if let Some(path) = args.iter().find_map(|a| a.strip_prefix("--replacements=")) {
+        replacements.extend(load_replacements(Path::new(path))?);
+    }
+
+    glossary.dedup();
+    Ok((glossary, replacements))
+}
+
+/// Last `max` characters of `text`, starting at a word boundary.
+fn tail(text: &str, max: usize) -> &str {
+    let text = text.trim();
+    if text.len() <= max {
+        return text;
+    }
+    let mut start = text.len() - max;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let cut = &text[start..];
+    match cut.find(char::is_whitespace) {
+        Some(i) => cut[i..].trim_start(),
+        None => cut,
+    }
+}
+
+/// Build the `prompt` field: glossary terms, then the tail of the previous
+/// chunk's text on the same channel so the model keeps style and spelling.
+pub fn build_prompt(glossary: &[String], previous: Option<&str>) -> Option<String> {
+    let context = previous.map(|p| tail(p, CONTEXT_CHARS)).unwrap_or_default();
+
+    let mut terms = String::new();
+    for term in glossary {
+        if terms.len() + term.len() + context.len() + 2 > MAX_PROMPT_CHARS {
+            break;
+        }
+        if !terms.is_empty() {
+            terms.push_str(", ");
+        }
+        terms.push_str(term);
+    }
+
+    let prompt = match (terms.is_empty(), context.is_empty()) {
+        (true, true) => return None,
+        (false, true) => format!("{terms}."),
+        (true, false) => context.to_string(),
+        (false, false) => format!("{terms}. {context}"),
+    };
+    Some(prompt)
+}
+
+/// Replace whole-word, ASCII case-insensitive matches of each `from` with `to`.
+pub fn apply(text: &str, replacements: &[Replacement]) -> String {
+    let mut out = text.to_string();
+    for (from, to) in replacements {
+        if from.is_empty() {
+            continue;
+        }
+        let needle = from.to_ascii_lowercase();
+        let mut result = String::with_capacity(out.len());
+        let mut rest = out.as_str();
+        loop {
+            let Some(i) = rest.to_ascii_lowercase().find(&needle) else {
+                result.push_str(rest);
+                break;
+            };
+            let end = i + needle.len();
+            let before_ok = rest[..i].chars().next_back().is_none_or(|c| !c.is_alphanumeric());
+            let after_ok = rest[end..].chars().next().is_none_or(|c| !c.is_alphanumeric());
+            result.push_str(&rest[..i]);
+            if before_ok && after_ok {
+                result.push_str(to);
+            } else {
+                result.push_str(&rest[i..end]);
+            }
+            rest = &rest[end..];
+        }
+        out = result;
+    }
+    out
+}